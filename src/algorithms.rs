@@ -1,11 +1,10 @@
 use image::RgbaImage;
-use rand::distr::{Alphanumeric, SampleString};
-use rand::{rng, Rng};
 
-use crate::generations::generate_initial_image;
-use crate::mutations::mutate_image;
-use crate::renderer::render_image;
+use crate::mutations::{adapt_step_size, mutate_image, MutationConfig, MutationType, StepSizeController};
+use crate::observer::{Control, Observer, RunStatus};
+use crate::restarts::{DefaultRestart, RestartConfig, RestartController, RestartStrategy};
 use crate::scoring::{score_images, CompareFn};
+use crate::stopping::{Criterion, Progress, StopCondition, StopTracker};
 use crate::types::Image;
 
 const MAX_ITERATIONS: usize = 100000;
@@ -16,112 +15,262 @@ const POPULATION_SIZE_INCREASE: usize = 1;
 const POPULATION_SIZE_INCREASE_FREQUENCY: usize = 1000;
 const MIN_MUTATIONS: usize = 2;
 const KEEP_TOP: usize = 3;
-const FILE_SAVE_FREQUENCY: usize = 1000;
+// Built-in stagnation response: shake every parent with a few mutations
+const PERTURB_MUTATIONS: usize = 5;
+const DEFAULT_RESTART: DefaultRestart = DefaultRestart {
+  strategy: RestartStrategy::Perturb {
+    mutations: PERTURB_MUTATIONS,
+    keep_best: 0,
+    members: None,
+  },
+  stagnation_threshold: Some(500),
+};
 
-pub fn strongest_mutates_alg(target: RgbaImage, compare_fn: CompareFn) -> Image {
+/// Parent genome and its score, kept until the genome changes so unchanged
+/// parents aren't rendered and scored again every iteration
+type Parent = (Option<f32>, Image);
+
+fn new_parents(mutation_config: &MutationConfig, width: u32, height: u32) -> Vec<Parent> {
+  (0..KEEP_TOP)
+    .map(|_| (None, mutation_config.initial_image(width as usize, height as usize)))
+    .collect()
+}
+
+fn perturb_parents(
+  parents: &mut [Parent],
+  keep_best: usize,
+  members: Option<usize>,
+  mutations: usize,
+  mutation_config: &MutationConfig,
+) {
+  let members = members.unwrap_or(parents.len());
+  for (score, parent) in parents.iter_mut().skip(keep_best).take(members) {
+    for _ in 0..mutations {
+      *parent = mutate_image(parent.clone(), mutation_config, None).0;
+    }
+    *score = None;
+  }
+}
+
+/// Score of the lead parent, 0 while unknown
+fn lead_score(parents: &[Parent]) -> f32 {
+  parents[0].0.unwrap_or(0.0)
+}
+
+/// Native ES. `stopping` defaults to MAX_ITERATIONS OR REQUIRED_ACCURACY.
+/// `observer` sees every iteration and may cancel the run.
+pub fn strongest_mutates_alg(
+  target: RgbaImage,
+  compare_fn: CompareFn,
+  mutation_config: &MutationConfig,
+  restart_config: &RestartConfig,
+  stopping: Option<&StopCondition>,
+  observer: &mut dyn Observer,
+) -> Image {
   let (width, height) = target.dimensions();
 
   // Start with initial parents
-  let mut parents: Vec<Image> = (0..KEEP_TOP)
-    .map(|_| generate_initial_image(width as usize, height as usize))
-    .collect();
+  let mut parents = new_parents(mutation_config, width, height);
+
+  // Local copy so step-size adaptation can tune it during the run
+  let mut mutation_config = mutation_config.clone();
+  let mut step_control = StepSizeController::new();
 
+  let mut restarts = RestartController::new(restart_config.clone());
+  let mut children_per_parent = CHILDREN_PER_PARENT;
+
+  // Best over the whole run, kept apart from the hall of fame, which may
+  // be disabled; and the best score since the last random restart
   let mut best_score = 0.0;
+  let mut best = parents[0].1.clone();
+  let mut run_best_score = 0.0;
   let mut iter_count: usize = 0;
-  let mut no_improvement_count: usize = 0;
+  let mut evaluations: usize = 0;
+
+  let mut stop = StopTracker::new(stopping.cloned().unwrap_or_else(|| {
+    StopCondition::any(vec![
+      Criterion::Iterations(MAX_ITERATIONS),
+      Criterion::Accuracy(REQUIRED_ACCURACY),
+    ])
+  }));
 
-  while iter_count < MAX_ITERATIONS && best_score < REQUIRED_ACCURACY {
+  while !stop.should_stop(&Progress {
+    iterations: iter_count,
+    evaluations,
+    accuracy: best_score,
+  }) {
     // Generate children from all parents
     let mut children: Vec<Image> = Vec::new();
+    // Last operator applied to each child, to report the one that takes the lead
+    let mut operators: Vec<Option<MutationType>> = Vec::new();
 
-    for parent in parents.iter() {
-      for _ in 0..CHILDREN_PER_PARENT {
-        let mut child = parent.clone();
+    for (_, parent) in parents.iter() {
+      for _ in 0..children_per_parent {
+        let mut child = adapt_step_size(parent.clone(), &mutation_config);
         let num_mutations = 1 + (iter_count / MUTATIONS_SCALING).min(MIN_MUTATIONS);
 
+        let mut operator = None;
         for _ in 0..num_mutations {
-          child = mutate_image(child);
+          let (mutated, applied) = mutate_image(child, &mutation_config, None);
+          child = mutated;
+          operator = Some(applied);
         }
 
         children.push(child);
+        operators.push(operator);
       }
     }
 
-    children.extend(parents.clone());
+    // Only parents changed since they were last scored need rendering
+    let (known, unknown): (Vec<Parent>, Vec<Parent>) = parents.drain(..).partition(|(score, _)| score.is_some());
+    children.extend(unknown.into_iter().map(|(_, img)| img));
+    operators.resize(children.len(), None);
+    evaluations += children.len();
 
     let mut scored = score_images(children, &target, compare_fn);
+    // Previously scored parents can't beat the run best, so on improvement
+    // the first of the highest scoring fresh genomes leads after the
+    // stable sort below
+    let fresh_best = scored.iter().map(|(score, _)| *score).fold(f32::MIN, f32::max);
+    let lead_operator = scored
+      .iter()
+      .position(|(score, _)| *score == fresh_best)
+      .and_then(|index| operators[index]);
+    scored.extend(known.into_iter().map(|(score, img)| (score.unwrap(), img)));
     scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
 
     let new_best_score = scored.first().map(|(s, _)| *s).unwrap_or(0.0);
+    let improved = new_best_score > run_best_score;
+    step_control.record(improved, &mut mutation_config);
+    if improved {
+      run_best_score = new_best_score;
+    }
     if new_best_score > best_score {
       best_score = new_best_score;
-      no_improvement_count = 0;
-    } else {
-      no_improvement_count += 1;
+      best = scored[0].1.clone();
     }
 
     // Take the two top performers as-is
-    let top_two: Vec<Image> = scored
+    let top_two: Vec<Parent> = scored
       .iter()
       .take(2)
-      .map(|(_, img)| img.clone())
+      .map(|(score, img)| (Some(*score), img.clone()))
       .collect();
+    let accepted_mutation = if improved { lead_operator } else { None };
 
     // Take the worst performer and apply mutations
     let mut worst = scored.last().map(|(_, img)| img.clone()).unwrap();
     for _ in 0..5 {
-      worst = mutate_image(worst);
+      worst = mutate_image(worst, &mutation_config, None).0;
     }
 
-    parents = vec![top_two[0].clone(), top_two[1].clone(), worst];
+    parents = top_two;
+    parents.push((None, worst));
 
-    if iter_count % POPULATION_SIZE_INCREASE_FREQUENCY == 0 {
+    if iter_count.is_multiple_of(POPULATION_SIZE_INCREASE_FREQUENCY) {
       // Increase population size to keep muliple of n best scoring parents
       parents.extend(
         scored
           .into_iter()
           .take(POPULATION_SIZE_INCREASE)
-          .map(|(_, img)| img),
+          .map(|(score, img)| (Some(score), img)),
       )
     };
 
-    if no_improvement_count > 500 {
-      for parent in parents.iter_mut() {
-        for _ in 0..5 {
-          *parent = mutate_image(parent.clone());
+    if restarts.should_restart(improved, false, Some(DEFAULT_RESTART)) {
+      restarts
+        .hall_of_fame
+        .insert(new_best_score, parents[0].1.clone());
+
+      match restarts.strategy(Some(DEFAULT_RESTART)).unwrap_or(DEFAULT_RESTART.strategy) {
+        RestartStrategy::Perturb {
+          mutations,
+          keep_best,
+          members,
+        } => {
+          perturb_parents(&mut parents, keep_best, members, mutations, &mutation_config);
+        }
+        // No temperature to reheat here; fall back to the built-in perturbation
+        RestartStrategy::Reheat { .. } => {
+          perturb_parents(&mut parents, 0, None, PERTURB_MUTATIONS, &mutation_config);
+        }
+        RestartStrategy::RandomRestart => {
+          parents = new_parents(&mutation_config, width, height);
+          run_best_score = 0.0;
+        }
+        RestartStrategy::Ipop { growth } => {
+          children_per_parent = ((children_per_parent as f32 * growth).ceil() as usize).max(1);
+          parents = new_parents(&mutation_config, width, height);
+          run_best_score = 0.0;
         }
       }
-      no_improvement_count = 0;
-    }
-
-    if iter_count % 500 == 0 {
-      println!(
-        "Iteration {}: accuracy = {:.4}% (stale: {})",
-        iter_count,
-        best_score * 100.0,
-        no_improvement_count
-      );
-    }
-    if iter_count % FILE_SAVE_FREQUENCY == 0 {
-      let rendered = render_image(&parents[0]);
-      let s: String = rng()
-        .sample_iter(Alphanumeric)
-        .take(7)
-        .map(char::from)
-        .collect();
-      rendered
-        .save("./resources/output/".to_string() + &s + ".png")
-        .unwrap();
     }
 
     iter_count += 1;
+
+    let status = RunStatus {
+      iteration: iter_count,
+      evaluations,
+      elapsed_seconds: stop.elapsed_seconds(),
+      best_score,
+      current_score: lead_score(&parents),
+      temperature: None,
+      accepted_mutation,
+      stagnation: restarts.stagnation(),
+      best: &best,
+    };
+    if observer.on_iteration(&status) == Control::Cancel {
+      break;
+    }
   }
 
-  println!(
-    "Finished after {} iterations with accuracy {:.4}%",
-    iter_count,
-    best_score * 100.0
-  );
+  observer.on_finish(&RunStatus {
+    iteration: iter_count,
+    evaluations,
+    elapsed_seconds: stop.elapsed_seconds(),
+    best_score,
+    current_score: lead_score(&parents),
+    temperature: None,
+    accepted_mutation: None,
+    stagnation: restarts.stagnation(),
+    best: &best,
+  });
 
-  parents.into_iter().next().unwrap()
+  best
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::renderer::render_image;
+  use crate::scoring::sad_compare;
+
+  #[test]
+  fn returns_the_genome_behind_the_reported_best_score() {
+    let target = RgbaImage::from_pixel(24, 16, image::Rgba([200, 40, 40, 255]));
+    let stopping = StopCondition::parse("iterations=30").unwrap();
+    // Restarting every iteration with no hall of fame discards each run's best
+    let restart_config = RestartConfig {
+      strategy: Some(RestartStrategy::RandomRestart),
+      stagnation_threshold: Some(0),
+      hall_of_fame_size: 0,
+      ..Default::default()
+    };
+    let mut reported = 0.0;
+    let mut observer = |status: &RunStatus| {
+      reported = status.best_score;
+      Control::Continue
+    };
+
+    let best = strongest_mutates_alg(
+      target.clone(),
+      sad_compare,
+      &MutationConfig::default(),
+      &restart_config,
+      Some(&stopping),
+      &mut observer,
+    );
+    assert!(reported > 0.0);
+    assert_eq!(sad_compare(&target, &render_image(&best)), reported);
+  }
 }
@@ -1,21 +1,103 @@
-use crate::generations::generate_initial_image;
-use crate::mutations::mutate_image;
-use crate::renderer_wasm::{render_image, PixelBuffer};
-use crate::scoring::sad_compare_raw;
-use crate::types::Image;
+use crate::cmaes::{Cmaes, CmaesConfig};
+use crate::colour_solve::solve_all_colours;
+use crate::constraints::PolygonConstraints;
+use crate::crossover::{binomial_crossover, crossover, CrossoverType};
+use crate::mutations::{adapt_step_size, mutate_image, MutationConfig, MutationType, StepSizeController};
+use crate::nsga2::{Individual, Nsga2, Nsga2Config};
+use crate::observer::{Control, Observer, RunStatus};
+use crate::palette::Palette;
+use crate::pso::{ParticleSwarm, PsoConfig};
+use crate::random::rng;
+use crate::renderer_wasm::{render_image_into, PixelBuffer};
+use crate::restarts::{DefaultRestart, HallOfFame, RestartConfig, RestartController, RestartStrategy};
+use crate::scoring::{Evaluator, Metric, TemporalPenalty};
+use crate::selection::{select_index, Selection};
+use crate::stopping::{Criterion, Progress, StopCondition, StopTracker};
+use crate::types::{Image, Polygon};
 use rand::Rng;
 
-#[derive(Clone, Copy, PartialEq)]
+// SA counts as frozen, and restarts, once cooled below this temperature
+const SA_MIN_TEMPERATURE: f32 = 0.0001;
+// Built-in restart strategies used when `RestartConfig::strategy` is None:
+// ES shakes its second parent after 500 stagnant iterations and SA reheats
+// once frozen. The other algorithms have none.
+const SA_DEFAULT_RESTART: DefaultRestart = DefaultRestart {
+  strategy: RestartStrategy::Reheat { temperature: 0.1 },
+  stagnation_threshold: None,
+};
+const ES_DEFAULT_RESTART: DefaultRestart = DefaultRestart {
+  strategy: RestartStrategy::Perturb {
+    mutations: PERTURB_MUTATIONS,
+    keep_best: 1,
+    members: Some(1),
+  },
+  stagnation_threshold: Some(500),
+};
+// Stands in for reheating in algorithms without a temperature
+const PERTURB_MUTATIONS: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum AlgorithmType {
   EvolutionStrategy,
   SimulatedAnnealing,
   DifferentialEvolution,
+  GeneticAlgorithm,
+  CmaEs,
+  ParticleSwarm,
+  Nsga2,
 }
 
+impl AlgorithmType {
+  pub fn name(self) -> &'static str {
+    match self {
+      AlgorithmType::EvolutionStrategy => "es",
+      AlgorithmType::SimulatedAnnealing => "sa",
+      AlgorithmType::DifferentialEvolution => "de",
+      AlgorithmType::GeneticAlgorithm => "ga",
+      AlgorithmType::CmaEs => "cmaes",
+      AlgorithmType::ParticleSwarm => "pso",
+      AlgorithmType::Nsga2 => "nsga2",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<AlgorithmType> {
+    match name {
+      "es" => Some(AlgorithmType::EvolutionStrategy),
+      "sa" => Some(AlgorithmType::SimulatedAnnealing),
+      "de" => Some(AlgorithmType::DifferentialEvolution),
+      "ga" => Some(AlgorithmType::GeneticAlgorithm),
+      "cmaes" => Some(AlgorithmType::CmaEs),
+      "pso" => Some(AlgorithmType::ParticleSwarm),
+      "nsga2" => Some(AlgorithmType::Nsga2),
+      _ => None,
+    }
+  }
+}
+
+/// How the GA replaces its population
+#[derive(Clone, Copy, PartialEq)]
+pub enum Replacement {
+  /// Breed a whole new generation each step, carrying over the elites
+  Generational,
+  /// Breed one child per step and let it replace the worst if it is better
+  SteadyState,
+}
+
+#[derive(Clone)]
 pub struct AlgorithmConfig {
   pub max_iterations: usize,
   pub target_accuracy: f32,
+  /// Replaces the two limits above when set, e.g. to stop on wall-clock
+  /// time, evaluations or a plateau
+  pub stopping: Option<StopCondition>,
   pub algorithm_type: AlgorithmType,
+  pub metric: Metric,
+  /// Penalise polygon movement away from a previous genome, as when
+  /// approximating the frames of a sequence
+  pub temporal: Option<TemporalPenalty>,
+  /// Share of the score given to matching Sobel edges rather than pixels,
+  /// from 0 (pixels only) to 1
+  pub edge_weight: f32,
   // ES specific
   pub es_children_per_parent: usize,
   // SA specific
@@ -25,6 +107,24 @@ pub struct AlgorithmConfig {
   pub population_size: usize,
   pub mutation_factor: f32,
   pub crossover_rate: f32,
+  // GA specific
+  pub ga_population_size: usize,
+  pub ga_selection: Selection,
+  pub ga_crossover_type: CrossoverType,
+  pub ga_crossover_rate: f32,
+  pub ga_mutation_rate: f32,
+  pub ga_elitism: usize,
+  pub ga_replacement: Replacement,
+  // CMA-ES specific
+  pub cma: CmaesConfig,
+  // PSO specific
+  pub pso: PsoConfig,
+  // NSGA-II specific
+  pub nsga2: Nsga2Config,
+  // Stagnation handling
+  pub restart: RestartConfig,
+  // Mutation operator weights and deltas
+  pub mutation: MutationConfig,
 }
 
 impl Default for AlgorithmConfig {
@@ -32,7 +132,11 @@ impl Default for AlgorithmConfig {
     Self {
       max_iterations: 100000,
       target_accuracy: 0.95,
+      stopping: None,
       algorithm_type: AlgorithmType::EvolutionStrategy,
+      metric: Metric::Sad,
+      temporal: None,
+      edge_weight: 0.0,
       // ES params
       es_children_per_parent: 5,
       // SA params
@@ -42,20 +146,53 @@ impl Default for AlgorithmConfig {
       population_size: 6,
       mutation_factor: 0.8,
       crossover_rate: 0.9,
+      // GA params
+      ga_population_size: 30,
+      ga_selection: Selection::Tournament { size: 3 },
+      ga_crossover_type: CrossoverType::Uniform,
+      ga_crossover_rate: 0.7,
+      ga_mutation_rate: 0.9,
+      ga_elitism: 2,
+      ga_replacement: Replacement::Generational,
+      // CMA-ES params
+      cma: CmaesConfig::default(),
+      // PSO params
+      pso: PsoConfig::default(),
+      // NSGA-II params
+      nsga2: Nsga2Config::default(),
+      restart: RestartConfig::default(),
+      mutation: MutationConfig::default(),
     }
   }
 }
 
+impl AlgorithmConfig {
+  /// `stopping`, or else the iteration limit OR the target accuracy
+  pub fn stop_condition(&self) -> StopCondition {
+    self.stopping.clone().unwrap_or_else(|| {
+      StopCondition::any(vec![
+        Criterion::Iterations(self.max_iterations),
+        Criterion::Accuracy(self.target_accuracy),
+      ])
+    })
+  }
+}
+
 pub struct AlgorithmState {
   config: AlgorithmConfig,
-  target_pixels: Vec<u8>,
+  evaluator: Evaluator,
   width: usize,
   height: usize,
   iteration: usize,
   finished: bool,
+  stop: StopTracker,
+  step_control: StepSizeController,
+  restarts: RestartController,
+  // Render of the best genome and the polygons it was drawn from, redrawn
+  // only when they change
+  best_render: Option<(Vec<Polygon>, PixelBuffer)>,
   // ES state (Evolution Strategy - original algorithm)
   es_parents: Vec<(f32, Image)>,
-  es_no_improvement: usize,
   // SA state
   sa_current: Option<Image>,
   sa_current_score: f32,
@@ -64,42 +201,65 @@ pub struct AlgorithmState {
   sa_temperature: f32,
   // DE state
   de_population: Vec<(f32, Image)>,
+  // GA state
+  ga_population: Vec<(f32, Image)>,
+  // CMA-ES state
+  cma: Option<Cmaes>,
+  // PSO state
+  pso: Option<ParticleSwarm>,
+  // NSGA-II state
+  nsga2: Option<Nsga2>,
 }
 
 impl AlgorithmState {
   pub fn new(target_pixels: Vec<u8>, width: usize, height: usize, config: AlgorithmConfig) -> Self {
     let mut state = Self {
-      target_pixels,
+      evaluator: Evaluator::new(target_pixels, config.metric)
+        .with_temporal_penalty(config.temporal.clone())
+        .with_edge_weight(config.edge_weight, width, height),
       width,
       height,
       iteration: 0,
       finished: false,
+      stop: StopTracker::new(config.stop_condition()),
+      step_control: StepSizeController::new(),
+      restarts: RestartController::new(config.restart.clone()),
+      best_render: None,
       es_parents: Vec::new(),
-      es_no_improvement: 0,
       sa_current: None,
       sa_current_score: 0.0,
       sa_best: None,
       sa_best_score: 0.0,
       sa_temperature: config.initial_temp,
       de_population: Vec::new(),
+      ga_population: Vec::new(),
+      cma: None,
+      pso: None,
+      nsga2: None,
       config,
     };
 
-    match state.config.algorithm_type {
-      AlgorithmType::EvolutionStrategy => state.init_es(),
-      AlgorithmType::SimulatedAnnealing => state.init_sa(),
-      AlgorithmType::DifferentialEvolution => state.init_de(),
-    }
-
+    state.init();
     state
   }
 
+  fn init(&mut self) {
+    match self.config.algorithm_type {
+      AlgorithmType::EvolutionStrategy => self.init_es(),
+      AlgorithmType::SimulatedAnnealing => self.init_sa(),
+      AlgorithmType::DifferentialEvolution => self.init_de(),
+      AlgorithmType::GeneticAlgorithm => self.init_ga(),
+      AlgorithmType::CmaEs => self.init_cma(),
+      AlgorithmType::ParticleSwarm => self.init_pso(),
+      AlgorithmType::Nsga2 => self.init_nsga2(),
+    }
+  }
+
   fn init_es(&mut self) {
     self.es_parents = (0..3)
       .map(|_| {
-        let img = generate_initial_image(self.width, self.height);
-        let rendered = render_image(&img);
-        let score = sad_compare_raw(&self.target_pixels, &rendered.data);
+        let img = self.config.mutation.initial_image(self.width, self.height);
+        let score = self.evaluator.score(&img);
         (score, img)
       })
       .collect();
@@ -109,9 +269,8 @@ impl AlgorithmState {
   }
 
   fn init_sa(&mut self) {
-    let img = generate_initial_image(self.width, self.height);
-    let rendered = render_image(&img);
-    let score = sad_compare_raw(&self.target_pixels, &rendered.data);
+    let img = self.config.mutation.initial_image(self.width, self.height);
+    let score = self.evaluator.score(&img);
     self.sa_current = Some(img.clone());
     self.sa_current_score = score;
     self.sa_best = Some(img);
@@ -122,9 +281,8 @@ impl AlgorithmState {
   fn init_de(&mut self) {
     self.de_population = (0..self.config.population_size)
       .map(|_| {
-        let img = generate_initial_image(self.width, self.height);
-        let rendered = render_image(&img);
-        let score = sad_compare_raw(&self.target_pixels, &rendered.data);
+        let img = self.config.mutation.initial_image(self.width, self.height);
+        let score = self.evaluator.score(&img);
         (score, img)
       })
       .collect();
@@ -133,103 +291,196 @@ impl AlgorithmState {
       .sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
   }
 
-  pub fn step_batch(&mut self, batch_size: usize) -> (bool, PixelBuffer) {
+  fn init_ga(&mut self) {
+    self.ga_population = (0..self.config.ga_population_size.max(2))
+      .map(|_| {
+        let img = self.config.mutation.initial_image(self.width, self.height);
+        let score = self.evaluator.score(&img);
+        (score, img)
+      })
+      .collect();
+    self
+      .ga_population
+      .sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+  }
+
+  fn init_cma(&mut self) {
+    let img = self.config.mutation.initial_image(self.width, self.height);
+    let score = self.evaluator.score(&img);
+    self.cma = Some(Cmaes::new(&img, score, &self.config.cma));
+  }
+
+  fn init_pso(&mut self) {
+    let img = self.config.mutation.initial_image(self.width, self.height);
+    let evaluator = &self.evaluator;
+    self.pso = Some(ParticleSwarm::new(&img, &self.config.pso, |img| {
+      evaluator.score(img)
+    }));
+  }
+
+  fn init_nsga2(&mut self) {
+    let evaluator = &self.evaluator;
+    self.nsga2 = Some(Nsga2::new(self.width, self.height, &self.config.nsga2, &self.config.mutation, |img| {
+      evaluator.score(img)
+    }));
+  }
+
+  pub fn step_batch(&mut self, batch_size: usize) -> (bool, &PixelBuffer) {
+    self.run_iterations(batch_size);
+    (self.finished, self.get_best_buffer())
+  }
+
+  /// Advance up to `iterations` steps without rendering the best image.
+  /// Returns whether the run has finished.
+  pub fn run_iterations(&mut self, iterations: usize) -> bool {
+    self.run_observed(iterations, &mut ())
+  }
+
+  /// `run_iterations`, reporting each iteration to `observer`, which may
+  /// cancel the run. `on_finish` is called when the run ends.
+  pub fn run_observed(&mut self, iterations: usize, observer: &mut dyn Observer) -> bool {
     if self.finished {
-      return (true, self.get_best_buffer());
+      return true;
     }
 
-    for _ in 0..batch_size {
-      let target_reached = self.get_accuracy() >= self.config.target_accuracy;
-      if self.iteration >= self.config.max_iterations || target_reached {
+    for _ in 0..iterations {
+      let progress = Progress {
+        iterations: self.iteration,
+        evaluations: self.evaluations(),
+        accuracy: self.get_accuracy(),
+      };
+      if self.stop.should_stop(&progress) {
         self.finished = true;
         break;
       }
 
-      match self.config.algorithm_type {
+      let old_best = self.current_best_score();
+      // DE, CMA-ES and PSO move genomes without the mutation operators
+      let accepted = match self.config.algorithm_type {
         AlgorithmType::EvolutionStrategy => self.step_es(),
         AlgorithmType::SimulatedAnnealing => self.step_sa(),
-        AlgorithmType::DifferentialEvolution => self.step_de(),
-      }
+        AlgorithmType::DifferentialEvolution => {
+          self.step_de();
+          None
+        }
+        AlgorithmType::GeneticAlgorithm => self.step_ga(),
+        AlgorithmType::CmaEs => {
+          self.step_cma();
+          None
+        }
+        AlgorithmType::ParticleSwarm => {
+          self.step_pso();
+          None
+        }
+        AlgorithmType::Nsga2 => self.step_nsga2(),
+      };
       self.iteration += 1;
+
+      let improved = self.current_best_score() > old_best;
+      let frozen = self.config.algorithm_type == AlgorithmType::SimulatedAnnealing
+        && self.sa_temperature < SA_MIN_TEMPERATURE;
+      if self.restarts.should_restart(improved, frozen, self.default_restart()) {
+        self.restart();
+      }
+
+      if observer.on_iteration(&self.status(accepted)) == Control::Cancel {
+        self.finished = true;
+        break;
+      }
     }
 
-    (self.finished, self.get_best_buffer())
+    if self.finished {
+      observer.on_finish(&self.status(None));
+    }
+    self.finished
+  }
+
+  pub(crate) fn status(&self, accepted_mutation: Option<MutationType>) -> RunStatus<'_> {
+    RunStatus {
+      iteration: self.iteration,
+      evaluations: self.evaluations(),
+      elapsed_seconds: self.stop.elapsed_seconds(),
+      best_score: self.get_accuracy(),
+      current_score: self.current_solution().0,
+      temperature: (self.config.algorithm_type == AlgorithmType::SimulatedAnnealing)
+        .then_some(self.sa_temperature),
+      accepted_mutation,
+      stagnation: self.restarts.stagnation(),
+      best: self.best_image(),
+    }
   }
 
-  fn step_es(&mut self) {
+  /// ES step. Returns the operator behind a new lead parent, if any
+  fn step_es(&mut self) -> Option<MutationType> {
     let old_best = self.es_parents[0].0;
+    let target = Some(self.evaluator.target_pixels());
 
-    let mut candidates: Vec<(f32, Image)> =
+    // Candidates with the operator that produced them; parents carry none
+    let mut candidates: Vec<(f32, Image, Option<MutationType>)> =
       Vec::with_capacity(3 * self.config.es_children_per_parent + 3);
 
     for (_, parent) in &self.es_parents {
       for _ in 0..self.config.es_children_per_parent {
-        let child = mutate_image(parent.clone());
-        let rendered = render_image(&child);
-        let score = sad_compare_raw(&self.target_pixels, &rendered.data);
-        candidates.push((score, child));
+        let child = adapt_step_size(parent.clone(), &self.config.mutation);
+        let (child, operator) = mutate_image(child, &self.config.mutation, target);
+        let score = self.evaluator.score(&child);
+        candidates.push((score, child, Some(operator)));
       }
     }
 
-    candidates.extend(self.es_parents.drain(..));
+    candidates.extend(self.es_parents.drain(..).map(|(score, img)| (score, img, None)));
 
     // Sort by score (best first)
     candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
 
-    let best = candidates.remove(0);
-    let second_best = candidates.remove(0);
+    let (best_score, best, operator) = candidates.remove(0);
+    let (second_score, second_best, _) = candidates.remove(0);
 
     // Take worst performer and mutate it heavily (survival of the fittest with a wildcard)
-    let (_, mut worst_img) = candidates.pop().unwrap_or_else(|| {
-      let img = generate_initial_image(self.width, self.height);
-      let rendered = render_image(&img);
-      let score = sad_compare_raw(&self.target_pixels, &rendered.data);
-      (score, img)
-    });
+    let mut worst_img = match candidates.pop() {
+      Some((_, img, _)) => img,
+      None => self.config.mutation.initial_image(self.width, self.height),
+    };
 
     // Apply random mutations to the worst to give it a fighting chance
     for _ in 0..5 {
-      worst_img = mutate_image(worst_img);
+      worst_img = mutate_image(worst_img, &self.config.mutation, target).0;
     }
-    let rendered = render_image(&worst_img);
-    let worst_score = sad_compare_raw(&self.target_pixels, &rendered.data);
+    let worst_score = self.evaluator.score(&worst_img);
 
-    self.es_parents = vec![best, second_best, (worst_score, worst_img)];
+    self.es_parents = vec![
+      (best_score, best),
+      (second_score, second_best),
+      (worst_score, worst_img),
+    ];
 
     // Track improvement
-    if self.es_parents[0].0 > old_best {
-      self.es_no_improvement = 0;
+    let improved = self.es_parents[0].0 > old_best;
+    self.step_control.record(improved, &mut self.config.mutation);
+    if best_score != old_best {
+      operator
     } else {
-      self.es_no_improvement += 1;
-    }
-
-    // If stuck, shake things up more aggressively
-    if self.es_no_improvement > 500 {
-      let (score, img) = &mut self.es_parents[1];
-      for _ in 0..20 {
-        *img = mutate_image(img.clone());
-      }
-      let rendered = render_image(img);
-      *score = sad_compare_raw(&self.target_pixels, &rendered.data);
-      self.es_no_improvement = 0;
+      None
     }
   }
 
-  /// Simulated Annealing step
-  fn step_sa(&mut self) {
+  /// Simulated Annealing step. Returns the operator behind an accepted move
+  fn step_sa(&mut self) -> Option<MutationType> {
     let current = self.sa_current.as_ref().unwrap();
 
-    let neighbor = mutate_image(current.clone());
-    let rendered = render_image(&neighbor);
-    let neighbor_score = sad_compare_raw(&self.target_pixels, &rendered.data);
+    let neighbor = adapt_step_size(current.clone(), &self.config.mutation);
+    let target = Some(self.evaluator.target_pixels());
+    let (neighbor, operator) = mutate_image(neighbor, &self.config.mutation, target);
+    let neighbor_score = self.evaluator.score(&neighbor);
 
     // Calculate acceptance probability
     let delta = neighbor_score - self.sa_current_score;
+    self.step_control.record(delta > 0.0, &mut self.config.mutation);
     let accept = if delta > 0.0 {
       true // Always accept improvements
     } else {
       let probability = (delta / self.sa_temperature).exp();
-      rand::rng().random::<f32>() < probability
+      rng().random::<f32>() < probability
     };
 
     if accept {
@@ -244,9 +495,127 @@ impl AlgorithmState {
     }
 
     self.sa_temperature *= self.config.cooling_rate;
+    accept.then_some(operator)
+  }
+
+  /// This algorithm's built-in restart strategy, if any
+  fn default_restart(&self) -> Option<DefaultRestart> {
+    match self.config.algorithm_type {
+      AlgorithmType::EvolutionStrategy => Some(ES_DEFAULT_RESTART),
+      AlgorithmType::SimulatedAnnealing => Some(SA_DEFAULT_RESTART),
+      _ => None,
+    }
+  }
+
+  /// Respond to stagnation with the configured (or built-in) restart strategy
+  fn restart(&mut self) {
+    let Some(strategy) = self.restarts.strategy(self.default_restart()) else {
+      return;
+    };
+
+    let best_score = self.current_best_score();
+    let best = self.current_best_image().clone();
+    self.restarts.hall_of_fame.insert(best_score, best);
+
+    match strategy {
+      RestartStrategy::Perturb {
+        mutations,
+        keep_best,
+        members,
+      } => self.perturb(mutations, keep_best, members),
+      RestartStrategy::Reheat { temperature } => {
+        if self.config.algorithm_type == AlgorithmType::SimulatedAnnealing {
+          self.sa_temperature = temperature;
+        } else {
+          self.perturb(PERTURB_MUTATIONS, 1, None);
+        }
+      }
+      RestartStrategy::RandomRestart => self.init(),
+      RestartStrategy::Ipop { growth } => {
+        let grow = |size: usize| ((size as f32 * growth).ceil() as usize).max(size);
+        match self.config.algorithm_type {
+          AlgorithmType::EvolutionStrategy => {
+            self.config.es_children_per_parent = grow(self.config.es_children_per_parent)
+          }
+          AlgorithmType::SimulatedAnnealing => {}
+          AlgorithmType::DifferentialEvolution => {
+            self.config.population_size = grow(self.config.population_size)
+          }
+          AlgorithmType::GeneticAlgorithm => {
+            self.config.ga_population_size = grow(self.config.ga_population_size)
+          }
+          AlgorithmType::CmaEs => {
+            let current = self.cma.as_ref().map(|c| c.population_size()).unwrap_or(0);
+            self.config.cma.population_size = grow(current);
+          }
+          AlgorithmType::ParticleSwarm => self.config.pso.swarm_size = grow(self.config.pso.swarm_size),
+          AlgorithmType::Nsga2 => {
+            self.config.nsga2.population_size = grow(self.config.nsga2.population_size)
+          }
+        }
+        self.init();
+      }
+    }
+  }
+
+  /// Mutate `members` members after the best `keep_best`, or all of them
+  /// for None. Fixed-topology algorithms instead restart their search
+  /// around the best genome.
+  fn perturb(&mut self, mutations: usize, keep_best: usize, members: Option<usize>) {
+    let evaluator = &self.evaluator;
+    let mutation = &self.config.mutation;
+    let shake = |img: &mut Image| {
+      for _ in 0..mutations {
+        *img = mutate_image(img.clone(), mutation, Some(evaluator.target_pixels())).0;
+      }
+      evaluator.score(img)
+    };
+
+    let members = members.unwrap_or(usize::MAX);
+    let perturb_population = |population: &mut Vec<(f32, Image)>| {
+      for (score, img) in population.iter_mut().skip(keep_best).take(members) {
+        *score = shake(img);
+      }
+    };
 
-    if self.sa_temperature < 0.0001 {
-      self.sa_temperature = 0.1;
+    match self.config.algorithm_type {
+      // step_es ranks parents together with their children
+      AlgorithmType::EvolutionStrategy => perturb_population(&mut self.es_parents),
+      AlgorithmType::DifferentialEvolution => {
+        perturb_population(&mut self.de_population);
+        self.de_population.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+      }
+      AlgorithmType::GeneticAlgorithm => {
+        perturb_population(&mut self.ga_population);
+        self.ga_population.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+      }
+      // Cooling starts over too; left frozen, SA would restart every iteration
+      AlgorithmType::SimulatedAnnealing => {
+        if let Some(current) = self.sa_current.as_mut() {
+          self.sa_current_score = shake(current);
+        }
+        self.sa_temperature = self.config.initial_temp;
+      }
+      AlgorithmType::CmaEs => {
+        if let Some(cma) = self.cma.as_ref() {
+          let (score, best) = cma.best();
+          self.cma = Some(Cmaes::new(&best.clone(), score, &self.config.cma));
+        }
+      }
+      AlgorithmType::ParticleSwarm => {
+        if let Some(pso) = self.pso.as_ref() {
+          let best = pso.best().1.clone();
+          self.pso = Some(ParticleSwarm::new(&best, &self.config.pso, |img| {
+            evaluator.score(img)
+          }));
+        }
+      }
+      // The Pareto front plays the part of the kept best members
+      AlgorithmType::Nsga2 => {
+        if let Some(nsga2) = self.nsga2.as_mut() {
+          nsga2.perturb(&shake);
+        }
+      }
     }
   }
 
@@ -259,7 +628,7 @@ impl AlgorithmState {
       let mut indices: Vec<usize> = (0..pop_size).filter(|&x| x != i).collect();
 
       // Shuffle and take 3
-      let mut rng = rand::rng();
+      let mut rng = rng();
       for j in (1..indices.len()).rev() {
         let k = rng.random_range(0..=j);
         indices.swap(j, k);
@@ -273,8 +642,7 @@ impl AlgorithmState {
       let target = &self.de_population[i].1;
 
       let trial = self.de_mutate_crossover(base, diff1, diff2, target);
-      let rendered = render_image(&trial);
-      let trial_score = sad_compare_raw(&self.target_pixels, &rendered.data);
+      let trial_score = self.evaluator.score(&trial);
 
       // Selection: keep better one
       if trial_score > self.de_population[i].0 {
@@ -290,7 +658,8 @@ impl AlgorithmState {
       .sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
   }
 
-  /// DE mutation and crossover: trial = base + F * (diff1 - diff2), then crossover with target
+  /// DE mutation and crossover: mutant = base + F * (diff1 - diff2), then
+  /// binomial crossover with target
   fn de_mutate_crossover(
     &self,
     base: &Image,
@@ -298,76 +667,200 @@ impl AlgorithmState {
     diff2: &Image,
     target: &Image,
   ) -> Image {
-    let mut trial = base.clone();
-    let mut rng = rand::rng();
+    let mut mutant = base.clone();
     let f = self.config.mutation_factor;
-    let cr = self.config.crossover_rate;
 
-    // For each polygon, apply DE operations
-    let min_len = trial
+    // Differences exist only where all three genomes have a polygon; the
+    // base's further polygons carry over unchanged
+    let min_len = mutant
       .polygon
       .len()
       .min(diff1.polygon.len())
-      .min(diff2.polygon.len())
-      .min(target.polygon.len());
+      .min(diff2.polygon.len());
 
     for i in 0..min_len {
-      if rng.random::<f32>() < cr {
-        let poly = &mut trial.polygon[i];
-
-        for c in 0..4 {
-          let base_c = base.polygon[i].colour[c] as f32;
-          let d1_c = diff1.polygon[i].colour[c] as f32;
-          let d2_c = diff2.polygon[i].colour[c] as f32;
-          let new_c = base_c + f * (d1_c - d2_c);
-          poly.colour[c] = new_c.clamp(0.0, 255.0) as u8;
-        }
+      let poly = &mut mutant.polygon[i];
+
+      for c in 0..4 {
+        let base_c = base.polygon[i].colour[c] as f32;
+        let d1_c = diff1.polygon[i].colour[c] as f32;
+        let d2_c = diff2.polygon[i].colour[c] as f32;
+        let new_c = base_c + f * (d1_c - d2_c);
+        poly.colour[c] = new_c.clamp(0.0, 255.0) as u8;
+      }
 
-        let min_points = poly
-          .points
-          .len()
-          .min(diff1.polygon[i].points.len())
-          .min(diff2.polygon[i].points.len());
-
-        for p in 0..min_points {
-          let base_x = base.polygon[i].points[p].0;
-          let base_y = base.polygon[i].points[p].1;
-          let d1_x = diff1.polygon[i].points[p].0;
-          let d1_y = diff1.polygon[i].points[p].1;
-          let d2_x = diff2.polygon[i].points[p].0;
-          let d2_y = diff2.polygon[i].points[p].1;
-
-          poly.points[p].0 = (base_x + f * (d1_x - d2_x)).clamp(0.0, self.width as f32 - 1.0);
-          poly.points[p].1 = (base_y + f * (d1_y - d2_y)).clamp(0.0, self.height as f32 - 1.0);
+      let min_points = poly
+        .points
+        .len()
+        .min(diff1.polygon[i].points.len())
+        .min(diff2.polygon[i].points.len());
+
+      for p in 0..min_points {
+        let base_x = base.polygon[i].points[p].0;
+        let base_y = base.polygon[i].points[p].1;
+        let d1_x = diff1.polygon[i].points[p].0;
+        let d1_y = diff1.polygon[i].points[p].1;
+        let d2_x = diff2.polygon[i].points[p].0;
+        let d2_y = diff2.polygon[i].points[p].1;
+
+        poly.points[p].0 = (base_x + f * (d1_x - d2_x)).clamp(0.0, self.width as f32 - 1.0);
+        poly.points[p].1 = (base_y + f * (d1_y - d2_y)).clamp(0.0, self.height as f32 - 1.0);
+      }
+    }
+
+    // Every slot of either genome, so the target's extra polygons can
+    // survive too
+    let trial = binomial_crossover(&mutant, target, self.config.crossover_rate);
+
+    // Vertex and colour arithmetic can break polygon constraints and
+    // leave the palette, which mutations can't
+    self.config.mutation.conform(trial)
+  }
+
+  /// Genetic algorithm step: one generation, or one child when steady-state.
+  /// Returns the operator behind a new best member, if any
+  fn step_ga(&mut self) -> Option<MutationType> {
+    let old_best = self.ga_population[0].0;
+    // Best child bred this step and the operator that produced it
+    let mut best_child: Option<(f32, MutationType)> = None;
+    let mut track = |score: f32, operator: Option<MutationType>| {
+      if let Some(operator) = operator.filter(|_| best_child.is_none_or(|(best, _)| score > best)) {
+        best_child = Some((score, operator));
+      }
+    };
+
+    match self.config.ga_replacement {
+      Replacement::Generational => {
+        let pop_size = self.ga_population.len();
+        let elites = self.config.ga_elitism.min(pop_size);
+
+        let mut next: Vec<(f32, Image)> = self.ga_population[..elites].to_vec();
+        while next.len() < pop_size {
+          let (score, child, operator) = self.ga_breed();
+          track(score, operator);
+          next.push((score, child));
+        }
+        self.ga_population = next;
+      }
+      Replacement::SteadyState => {
+        let (score, child, operator) = self.ga_breed();
+        let worst = self.ga_population.last_mut().unwrap();
+        if score > worst.0 {
+          track(score, operator);
+          *worst = (score, child);
         }
-      } else {
-        // Keep target's polygon
-        trial.polygon[i] = target.polygon[i].clone();
       }
     }
 
-    trial
+    self
+      .ga_population
+      .sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+    let new_best = self.ga_population[0].0;
+    self.step_control.record(new_best > old_best, &mut self.config.mutation);
+    best_child
+      .filter(|(score, _)| new_best != old_best && *score == new_best)
+      .map(|(_, operator)| operator)
   }
 
-  fn get_best_buffer(&self) -> PixelBuffer {
-    match self.config.algorithm_type {
-      AlgorithmType::EvolutionStrategy => render_image(&self.es_parents[0].1),
-      AlgorithmType::SimulatedAnnealing => render_image(self.sa_best.as_ref().unwrap()),
-      AlgorithmType::DifferentialEvolution => render_image(&self.de_population[0].1),
+  /// CMA-ES step: one generation over the fixed topology of the initial genome
+  fn step_cma(&mut self) {
+    let evaluator = &self.evaluator;
+    if let Some(cma) = self.cma.as_mut() {
+      cma.step(|img| evaluator.score(img));
+    }
+  }
+
+  /// PSO step: move every particle once
+  fn step_pso(&mut self) {
+    let evaluator = &self.evaluator;
+    if let Some(pso) = self.pso.as_mut() {
+      pso.step(|img| evaluator.score(img));
+    }
+  }
+
+  /// NSGA-II step: one generation trading accuracy against genome size.
+  /// Returns the operator behind a new most accurate member, if any
+  fn step_nsga2(&mut self) -> Option<MutationType> {
+    let evaluator = &self.evaluator;
+    let nsga2 = self.nsga2.as_mut()?;
+    let old_best = nsga2.best().0;
+    let target = evaluator.target_pixels();
+    let operator = nsga2.step(&self.config.mutation, target, |img| evaluator.score(img));
+    operator.filter(|_| nsga2.best().0 != old_best)
+  }
+
+  /// Breed and score a single GA child from the current population, with
+  /// the operator applied if it was mutated
+  fn ga_breed(&self) -> (f32, Image, Option<MutationType>) {
+    let mut rng = rng();
+    let selection = self.config.ga_selection;
+
+    let first = &self.ga_population[select_index(&self.ga_population, selection)].1;
+    let mut child = if rng.random::<f32>() < self.config.ga_crossover_rate {
+      let second = &self.ga_population[select_index(&self.ga_population, selection)].1;
+      crossover(first, second, self.config.ga_crossover_type, self.config.mutation.min_polygons)
+    } else {
+      first.clone()
+    };
+
+    let mut operator = None;
+    if rng.random::<f32>() < self.config.ga_mutation_rate {
+      let adapted = adapt_step_size(child, &self.config.mutation);
+      let target = Some(self.evaluator.target_pixels());
+      let (mutated, applied) = mutate_image(adapted, &self.config.mutation, target);
+      child = mutated;
+      operator = Some(applied);
+    }
+
+    let score = self.evaluator.score(&child);
+    (score, child, operator)
+  }
+
+  /// Render of the best genome, reusing the last one while the best
+  /// genome's polygons are unchanged
+  fn get_best_buffer(&mut self) -> &PixelBuffer {
+    let best = self.best_image();
+    let stale = !matches!(&self.best_render, Some((polygons, _)) if *polygons == best.polygon);
+    if stale {
+      let polygons = best.polygon.clone();
+      let mut buffer = match self.best_render.take() {
+        Some((_, buffer)) => buffer,
+        None => PixelBuffer::new(self.width, self.height),
+      };
+      render_image_into(self.best_image(), &mut buffer);
+      self.best_render = Some((polygons, buffer));
     }
+    &self.best_render.as_ref().unwrap().1
   }
 
   pub fn get_iteration(&self) -> usize {
     self.iteration
   }
 
+  /// Fitness evaluations (renders scored) so far, including initialisation
+  pub fn evaluations(&self) -> usize {
+    self.evaluator.evaluations()
+  }
+
   pub fn get_accuracy(&self) -> f32 {
+    let archived = self.restarts.hall_of_fame.best().map(|(s, _)| s).unwrap_or(0.0);
+    self.current_best_score().max(archived)
+  }
+
+  fn current_best_score(&self) -> f32 {
     match self.config.algorithm_type {
       AlgorithmType::EvolutionStrategy => self.es_parents.first().map(|(s, _)| *s).unwrap_or(0.0),
       AlgorithmType::SimulatedAnnealing => self.sa_best_score,
       AlgorithmType::DifferentialEvolution => {
         self.de_population.first().map(|(s, _)| *s).unwrap_or(0.0)
       }
+      AlgorithmType::GeneticAlgorithm => {
+        self.ga_population.first().map(|(s, _)| *s).unwrap_or(0.0)
+      }
+      AlgorithmType::CmaEs => self.cma.as_ref().map(|c| c.best().0).unwrap_or(0.0),
+      AlgorithmType::ParticleSwarm => self.pso.as_ref().map(|p| p.best().0).unwrap_or(0.0),
+      AlgorithmType::Nsga2 => self.nsga2.as_ref().map(|n| n.best().0).unwrap_or(0.0),
     }
   }
 
@@ -378,4 +871,186 @@ impl AlgorithmState {
   pub fn get_dimensions(&self) -> (usize, usize) {
     (self.width, self.height)
   }
+
+  /// Best genome found so far, including any archived before a restart
+  pub fn best_image(&self) -> &Image {
+    match self.restarts.hall_of_fame.best() {
+      Some((score, img)) if score > self.current_best_score() => img,
+      _ => self.current_best_image(),
+    }
+  }
+
+  pub fn hall_of_fame(&self) -> &HallOfFame {
+    &self.restarts.hall_of_fame
+  }
+
+  /// Solution the search is working from: the SA state, otherwise the
+  /// best member since the last restart
+  fn current_solution(&self) -> (f32, &Image) {
+    match self.config.algorithm_type {
+      AlgorithmType::SimulatedAnnealing => (self.sa_current_score, self.sa_current.as_ref().unwrap()),
+      _ => (self.current_best_score(), self.current_best_image()),
+    }
+  }
+
+  /// Best genome of the current search, since the last restart
+  fn current_best_image(&self) -> &Image {
+    match self.config.algorithm_type {
+      AlgorithmType::EvolutionStrategy => &self.es_parents[0].1,
+      AlgorithmType::SimulatedAnnealing => self.sa_best.as_ref().unwrap(),
+      AlgorithmType::DifferentialEvolution => &self.de_population[0].1,
+      AlgorithmType::GeneticAlgorithm => &self.ga_population[0].1,
+      AlgorithmType::CmaEs => self.cma.as_ref().unwrap().best().1,
+      AlgorithmType::ParticleSwarm => self.pso.as_ref().unwrap().best().1,
+      AlgorithmType::Nsga2 => self.nsga2.as_ref().unwrap().best().1,
+    }
+  }
+
+  pub fn algorithm_type(&self) -> AlgorithmType {
+    self.config.algorithm_type
+  }
+
+  /// Bring in a genome from outside this run, e.g. a migrant from another
+  /// island. It is scored here and replaces the weakest member when better;
+  /// fixed-topology algorithms ignore genomes of a different topology.
+  pub fn inject(&mut self, image: Image) {
+    let score = self.evaluator.score(&image);
+
+    let replace_worst = |population: &mut Vec<(f32, Image)>, image: Image| {
+      if let Some(worst) = population.last_mut() {
+        if score > worst.0 {
+          *worst = (score, image);
+          population.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        }
+      }
+    };
+
+    match self.config.algorithm_type {
+      AlgorithmType::EvolutionStrategy => replace_worst(&mut self.es_parents, image),
+      AlgorithmType::DifferentialEvolution => replace_worst(&mut self.de_population, image),
+      AlgorithmType::GeneticAlgorithm => replace_worst(&mut self.ga_population, image),
+      AlgorithmType::SimulatedAnnealing => {
+        if score > self.sa_current_score {
+          self.sa_current = Some(image.clone());
+          self.sa_current_score = score;
+        }
+        if score > self.sa_best_score {
+          self.sa_best = Some(image);
+          self.sa_best_score = score;
+        }
+      }
+      AlgorithmType::CmaEs => {
+        if let Some(cma) = self.cma.as_mut() {
+          cma.recentre(&image, score);
+        }
+      }
+      AlgorithmType::ParticleSwarm => {
+        if let Some(pso) = self.pso.as_mut() {
+          pso.replace_worst(&image, score);
+        }
+      }
+      AlgorithmType::Nsga2 => {
+        if let Some(nsga2) = self.nsga2.as_mut() {
+          nsga2.insert(image, score);
+        }
+      }
+    }
+  }
+
+  /// Least-squares fit every colour of the best genome for its current
+  /// geometry, snap them to any palette, then offer the result to the
+  /// search like a migrant
+  pub fn solve_colours(&mut self, sweeps: usize) {
+    let solved = solve_all_colours(self.best_image().clone(), self.evaluator.target_pixels(), sweeps);
+    self.inject(self.config.mutation.conform(solved));
+  }
+
+  /// Accuracy against genome size trade-offs found by NSGA-II, smallest
+  /// genome first; empty for single-objective algorithms
+  pub fn pareto_front(&self) -> Vec<&Individual> {
+    self.nsga2.as_ref().map(|n| n.front()).unwrap_or_default()
+  }
+
+  /// Replace the stopping rule; the wall clock restarts from now
+  pub fn set_stop_condition(&mut self, condition: StopCondition) {
+    self.stop = StopTracker::new(condition.clone());
+    self.config.stopping = Some(condition);
+    self.finished = false;
+  }
+
+  /// Replace the restart settings; call before stepping, as this also
+  /// resets the stagnation count, restart budget and hall of fame
+  pub fn set_restart_config(&mut self, restart: RestartConfig) {
+    self.restarts = RestartController::new(restart.clone());
+    self.config.restart = restart;
+  }
+
+  /// Replace the polygon constraints and regenerate the population to meet
+  /// them; call before stepping
+  pub fn set_polygon_constraints(&mut self, constraints: PolygonConstraints) {
+    self.config.mutation.constraints = constraints;
+    self.init();
+  }
+
+  /// Restrict colours to `palette`, or lift the restriction with None, and
+  /// regenerate the population to match; call before stepping
+  pub fn set_palette(&mut self, palette: Option<Palette>) {
+    self.config.mutation.palette = palette;
+    self.init();
+  }
+
+  /// Replace the edge term's weight and regenerate the population, as its
+  /// scores change; call before stepping
+  pub fn set_edge_weight(&mut self, weight: f32) {
+    self.config.edge_weight = weight;
+    self.evaluator.set_edge_weight(weight, self.width, self.height);
+    self.init();
+  }
+
+  /// Pixels of the target being approximated
+  pub fn target_pixels(&self) -> &[u8] {
+    self.evaluator.target_pixels()
+  }
+
+  pub fn mutation_config_mut(&mut self) -> &mut MutationConfig {
+    &mut self.config.mutation
+  }
+
+  /// Settings read afresh every step, such as the GA's selection, rates and
+  /// elitism. Population sizes are read when the population is created, so
+  /// change them with their own setters.
+  pub fn config_mut(&mut self) -> &mut AlgorithmConfig {
+    &mut self.config
+  }
+
+  /// Replace the GA population size and regenerate the population; call
+  /// before stepping
+  pub fn set_ga_population_size(&mut self, size: usize) {
+    self.config.ga_population_size = size;
+    self.init();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn perturbed_sa_leaves_the_frozen_state() {
+    let mut config = AlgorithmConfig {
+      algorithm_type: AlgorithmType::SimulatedAnnealing,
+      initial_temp: 1.0,
+      cooling_rate: 0.5,
+      ..Default::default()
+    };
+    config.restart.strategy = Some(RestartStrategy::from_name("perturb").unwrap());
+    let (width, height) = (12, 8);
+    let mut state = AlgorithmState::new(vec![128; width * height * 4], width, height, config);
+
+    // Freezes after about 14 iterations of halving
+    state.run_iterations(300);
+    let restarts = state.restarts.restarts();
+    assert!(restarts > 0);
+    assert!(restarts <= 300 / 10, "{} restarts in 300 iterations", restarts);
+  }
 }
@@ -0,0 +1,294 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+const TARGET_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "bmp", "gif"];
+
+/// One target and where its outputs go
+#[derive(Clone, Debug)]
+pub struct BatchJob {
+  pub input: PathBuf,
+  /// Output path without extension; outputs are this plus .png, .genome, ...
+  pub output: PathBuf,
+}
+
+impl BatchJob {
+  pub fn with_extension(&self, extension: &str) -> PathBuf {
+    let mut path = self.output.clone().into_os_string();
+    path.push(".");
+    path.push(extension);
+    path.into()
+  }
+
+  /// The genome is written last, so its presence marks a finished target
+  pub fn is_complete(&self) -> bool {
+    self.with_extension("genome").exists()
+  }
+}
+
+/// What approximating one target produced
+#[derive(Clone, Copy, Debug)]
+pub struct JobOutcome {
+  pub accuracy: f32,
+  pub iterations: usize,
+  pub evaluations: usize,
+}
+
+#[derive(Clone, Debug)]
+pub enum JobStatus {
+  Done(JobOutcome),
+  /// Completed by an earlier batch; the accuracy is re-measured when possible
+  Skipped(Option<f32>),
+  Failed(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct JobReport {
+  pub input: PathBuf,
+  pub status: JobStatus,
+  pub seconds: f64,
+}
+
+fn is_target(path: &Path) -> bool {
+  path
+    .extension()
+    .and_then(|e| e.to_str())
+    .is_some_and(|e| TARGET_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Collect files under `dir`, descending at most `depth` directories
+fn walk(dir: &Path, depth: Option<usize>, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+  for entry in std::fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.is_dir() {
+      match depth {
+        Some(0) => {}
+        _ => walk(&path, depth.map(|d| d - 1), files)?,
+      }
+    } else {
+      files.push(path);
+    }
+  }
+  Ok(())
+}
+
+/// Match `text` against a pattern where `*` matches within one path
+/// component, `**` across components and `?` any one character
+fn wildcard_match(pattern: &[char], text: &[char]) -> bool {
+  match pattern {
+    [] => text.is_empty(),
+    ['*', '*', rest @ ..] => {
+      // "**/" may also match no directories at all
+      let rest = rest.strip_prefix(&['/']).unwrap_or(rest);
+      (0..=text.len()).any(|i| wildcard_match(rest, &text[i..]))
+    }
+    ['*', rest @ ..] => (0..=text.len())
+      .take_while(|&i| i == 0 || text[i - 1] != '/')
+      .any(|i| wildcard_match(rest, &text[i..])),
+    ['?', rest @ ..] => text.first().is_some_and(|&c| c != '/') && wildcard_match(rest, &text[1..]),
+    [c, rest @ ..] => text.first() == Some(c) && wildcard_match(rest, &text[1..]),
+  }
+}
+
+/// Jobs for every image under a directory, or matching a glob such as
+/// `photos/**/*.png`. Outputs mirror the inputs' paths relative to the
+/// directory (or the glob's fixed prefix) inside `output_dir`, minus the
+/// extension, so inputs differing only in extension are an error.
+pub fn find_jobs(spec: &str, output_dir: &Path) -> Result<Vec<BatchJob>, String> {
+  let (base, pattern) = match spec.find(['*', '?']) {
+    Some(wildcard) => {
+      let split = spec[..wildcard].rfind('/').map(|i| i + 1).unwrap_or(0);
+      let base = if split == 0 { "." } else { &spec[..split] };
+      (PathBuf::from(base), Some(spec[split..].chars().collect::<Vec<char>>()))
+    }
+    None => (PathBuf::from(spec), None),
+  };
+  if !base.is_dir() {
+    return Err(format!("{} is not a directory or glob", spec));
+  }
+
+  // Without "**" a glob can only match as deep as it has separators
+  let depth = pattern
+    .as_ref()
+    .filter(|p| !p.windows(2).any(|w| w == ['*', '*']))
+    .map(|p| p.iter().filter(|&&c| c == '/').count());
+
+  let mut files = Vec::new();
+  walk(&base, depth, &mut files).map_err(|err| format!("Cannot read {}: {}", base.display(), err))?;
+  files.sort();
+
+  let jobs: Vec<BatchJob> = files
+    .into_iter()
+    // Outputs may be written inside the input tree
+    .filter(|path| is_target(path) && !path.starts_with(output_dir))
+    .filter_map(|path| {
+      let relative = path.strip_prefix(&base).ok()?.to_path_buf();
+      if let Some(pattern) = &pattern {
+        let text: Vec<char> = relative.to_string_lossy().replace('\\', "/").chars().collect();
+        if !wildcard_match(pattern, &text) {
+          return None;
+        }
+      }
+      Some(BatchJob {
+        output: output_dir.join(relative.with_extension("")),
+        input: path,
+      })
+    })
+    .collect();
+
+  if jobs.is_empty() {
+    return Err(format!("No target images found for {}", spec));
+  }
+  // e.g. red.png and red.jpg would write the same outputs
+  let mut by_output: Vec<&BatchJob> = jobs.iter().collect();
+  by_output.sort_by(|a, b| a.output.cmp(&b.output));
+  if let Some(pair) = by_output.windows(2).find(|pair| pair[0].output == pair[1].output) {
+    return Err(format!(
+      "{} and {} would share the outputs {}.*; rename one or narrow the glob",
+      pair[0].input.display(),
+      pair[1].input.display(),
+      pair[0].output.display()
+    ));
+  }
+  Ok(jobs)
+}
+
+/// Run `approximate` on every job not already complete using `workers`
+/// threads. `remeasure` gives the accuracy of a completed job's saved
+/// genome, and `on_report` is called as each job finishes. Reports come
+/// back in job order.
+pub fn run_batch<A, M, R>(jobs: &[BatchJob], workers: usize, approximate: A, remeasure: M, on_report: R) -> Vec<JobReport>
+where
+  A: Fn(&BatchJob) -> Result<JobOutcome, String> + Sync,
+  M: Fn(&BatchJob) -> Option<f32> + Sync,
+  R: Fn(&JobReport) + Sync,
+{
+  let next = AtomicUsize::new(0);
+  let reports: Mutex<Vec<Option<JobReport>>> = Mutex::new(vec![None; jobs.len()]);
+
+  thread::scope(|scope| {
+    for _ in 0..workers.clamp(1, jobs.len().max(1)) {
+      scope.spawn(|| loop {
+        let index = next.fetch_add(1, Ordering::Relaxed);
+        let Some(job) = jobs.get(index) else {
+          break;
+        };
+
+        let start = Instant::now();
+        let status = if job.is_complete() {
+          JobStatus::Skipped(remeasure(job))
+        } else {
+          match approximate(job) {
+            Ok(outcome) => JobStatus::Done(outcome),
+            Err(err) => JobStatus::Failed(err),
+          }
+        };
+        let report = JobReport {
+          input: job.input.clone(),
+          status,
+          seconds: start.elapsed().as_secs_f64(),
+        };
+
+        on_report(&report);
+        reports.lock().unwrap()[index] = Some(report);
+      });
+    }
+  });
+
+  reports.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+fn csv_field(value: &str) -> String {
+  if value.contains([',', '"', '\n']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value.to_string()
+  }
+}
+
+/// One row per job
+pub fn summary_csv(reports: &[JobReport]) -> String {
+  let mut csv = String::from("input,status,accuracy,iterations,evaluations,seconds,error\n");
+  for report in reports {
+    let input = csv_field(&report.input.to_string_lossy());
+    let row = match &report.status {
+      JobStatus::Done(outcome) => format!(
+        "{},done,{},{},{},{},",
+        input, outcome.accuracy, outcome.iterations, outcome.evaluations, report.seconds
+      ),
+      JobStatus::Skipped(accuracy) => {
+        let accuracy = accuracy.map(|a| a.to_string()).unwrap_or_default();
+        format!("{},skipped,{},,,,", input, accuracy)
+      }
+      JobStatus::Failed(err) => format!("{},failed,,,,{},{}", input, report.seconds, csv_field(err)),
+    };
+    csv.push_str(&row);
+    csv.push('\n');
+  }
+  csv
+}
+
+/// Counts of done, skipped and failed jobs and the mean accuracy of those
+/// with one
+pub fn summary_line(reports: &[JobReport]) -> String {
+  let (mut done, mut skipped, mut failed) = (0, 0, 0);
+  let mut accuracies = Vec::new();
+  for report in reports {
+    match &report.status {
+      JobStatus::Done(outcome) => {
+        done += 1;
+        accuracies.push(outcome.accuracy);
+      }
+      JobStatus::Skipped(accuracy) => {
+        skipped += 1;
+        accuracies.extend(accuracy);
+      }
+      JobStatus::Failed(_) => failed += 1,
+    }
+  }
+
+  let mean = accuracies.iter().sum::<f32>() / accuracies.len().max(1) as f32;
+  format!(
+    "{} done, {} skipped, {} failed; mean accuracy {:.4}%",
+    done,
+    skipped,
+    failed,
+    mean * 100.0
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    wildcard_match(&pattern, &text)
+  }
+
+  #[test]
+  fn star_stays_within_a_component() {
+    assert!(matches("*.png", "red.png"));
+    assert!(!matches("*.png", "shoes/red.png"));
+    assert!(matches("shoes/*.png", "shoes/red.png"));
+    assert!(!matches("*.png", "red.jpg"));
+  }
+
+  #[test]
+  fn double_star_crosses_components() {
+    assert!(matches("**/*.png", "red.png"));
+    assert!(matches("**/*.png", "shoes/summer/red.png"));
+    assert!(matches("shoes/**", "shoes/summer/red.png"));
+    assert!(!matches("hats/**", "shoes/red.png"));
+  }
+
+  #[test]
+  fn question_mark_matches_one_character() {
+    assert!(matches("red?.png", "red1.png"));
+    assert!(!matches("red?.png", "red.png"));
+    assert!(!matches("a?b", "a/b"));
+  }
+}
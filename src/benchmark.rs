@@ -0,0 +1,482 @@
+use std::time::Instant;
+
+use image::RgbaImage;
+
+use crate::algorithms::strongest_mutates_alg;
+use crate::algorithms_wasm::{AlgorithmConfig, AlgorithmState, AlgorithmType};
+use crate::observer::{Control, RunStatus};
+use crate::random;
+use crate::renderer;
+use crate::renderer_wasm::render_image;
+use crate::scoring::{mse_compare, sad_compare, sad_compare_raw, Metric};
+use crate::stopping::{Criterion, StopCondition};
+
+/// What a case runs: the native strongest loop, or a shared algorithm
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BenchmarkAlgorithm {
+  Strongest,
+  Shared(AlgorithmType),
+}
+
+impl BenchmarkAlgorithm {
+  pub fn name(self) -> &'static str {
+    match self {
+      BenchmarkAlgorithm::Strongest => "strongest",
+      BenchmarkAlgorithm::Shared(algorithm_type) => algorithm_type.name(),
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<BenchmarkAlgorithm> {
+    match name {
+      "strongest" => Some(BenchmarkAlgorithm::Strongest),
+      _ => AlgorithmType::from_name(name).map(BenchmarkAlgorithm::Shared),
+    }
+  }
+}
+
+/// One algorithm configuration to compare. `config.algorithm_type` is
+/// ignored in favour of `algorithm`.
+#[derive(Clone)]
+pub struct BenchmarkCase {
+  pub label: String,
+  pub algorithm: BenchmarkAlgorithm,
+  pub config: AlgorithmConfig,
+}
+
+pub struct BenchmarkTarget {
+  pub name: String,
+  pub pixels: Vec<u8>,
+  pub width: usize,
+  pub height: usize,
+}
+
+pub struct BenchmarkConfig {
+  pub cases: Vec<BenchmarkCase>,
+  pub targets: Vec<BenchmarkTarget>,
+  /// Every case runs once per target and seed
+  pub seeds: Vec<u64>,
+  /// Fitness evaluations per run; a run stops early only if its own
+  /// stopping rule is met first. The budget is checked between
+  /// iterations, so a run can exceed it by up to one iteration's
+  /// evaluations, a whole generation for GA and NSGA-II.
+  pub evaluation_budget: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct RunResult {
+  pub case: String,
+  pub algorithm: BenchmarkAlgorithm,
+  pub metric: Metric,
+  pub target: String,
+  pub seed: u64,
+  /// SAD accuracy of the best genome, whatever metric was optimised, so
+  /// every case is measured on the same scale
+  pub accuracy: f32,
+  pub evaluations: usize,
+  pub iterations: usize,
+  pub seconds: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct CaseSummary {
+  pub case: String,
+  pub algorithm: BenchmarkAlgorithm,
+  pub metric: Metric,
+  /// Target name, or "all" for the summary over every target
+  pub target: String,
+  pub runs: usize,
+  pub mean_accuracy: f32,
+  pub std_accuracy: f32,
+  /// Mean evaluations per run, which may exceed the budget; see
+  /// `BenchmarkConfig::evaluation_budget`
+  pub mean_evaluations: f64,
+  pub mean_seconds: f64,
+  pub evaluations_per_second: f64,
+}
+
+/// Run every case on every target with every seed, sequentially so wall
+/// times are comparable. `on_run` is called after each run finishes.
+pub fn run_benchmark<F: FnMut(&RunResult)>(config: &BenchmarkConfig, mut on_run: F) -> Vec<RunResult> {
+  let mut results = Vec::new();
+
+  for case in &config.cases {
+    for target in &config.targets {
+      for &seed in &config.seeds {
+        let result = run_once(case, target, seed, config.evaluation_budget);
+        on_run(&result);
+        results.push(result);
+      }
+    }
+  }
+
+  results
+}
+
+fn run_once(case: &BenchmarkCase, target: &BenchmarkTarget, seed: u64, budget: usize) -> RunResult {
+  random::seed(seed);
+  let start = Instant::now();
+
+  let mut config = case.config.clone();
+  let budget = StopCondition::any(vec![Criterion::Evaluations(budget)]);
+  config.stopping = Some(config.stop_condition().or(budget));
+
+  // Each measured with the renderer its output would be saved with
+  let (accuracy, evaluations, iterations) = match case.algorithm {
+    BenchmarkAlgorithm::Strongest => {
+      let (width, height) = (target.width as u32, target.height as u32);
+      let image = RgbaImage::from_raw(width, height, target.pixels.clone())
+        .expect("target pixels match its dimensions");
+      let compare_fn = match config.metric {
+        Metric::Sad => sad_compare,
+        Metric::Mse => mse_compare,
+      };
+      let (mut evaluations, mut iterations) = (0, 0);
+      let mut observer = |status: &RunStatus| {
+        (evaluations, iterations) = (status.evaluations, status.iteration);
+        Control::Continue
+      };
+      let best = strongest_mutates_alg(
+        image.clone(),
+        compare_fn,
+        &config.mutation,
+        &config.restart,
+        config.stopping.as_ref(),
+        &mut observer,
+      );
+      (sad_compare(&image, &renderer::render_image(&best)), evaluations, iterations)
+    }
+    BenchmarkAlgorithm::Shared(algorithm_type) => {
+      config.algorithm_type = algorithm_type;
+      let mut state =
+        AlgorithmState::new(target.pixels.clone(), target.width, target.height, config);
+      state.run_iterations(usize::MAX);
+      let rendered = render_image(state.best_image());
+      (
+        sad_compare_raw(&target.pixels, &rendered.data),
+        state.evaluations(),
+        state.get_iteration(),
+      )
+    }
+  };
+
+  RunResult {
+    case: case.label.clone(),
+    algorithm: case.algorithm,
+    metric: case.config.metric,
+    target: target.name.clone(),
+    seed,
+    accuracy,
+    evaluations,
+    iterations,
+    seconds: start.elapsed().as_secs_f64(),
+  }
+}
+
+fn summarise_runs(runs: &[&RunResult], target: &str) -> CaseSummary {
+  let n = runs.len();
+  let mean_accuracy = runs.iter().map(|r| r.accuracy).sum::<f32>() / n as f32;
+  // Sample standard deviation; zero for a single run
+  let variance = if n > 1 {
+    runs
+      .iter()
+      .map(|r| (r.accuracy - mean_accuracy).powi(2))
+      .sum::<f32>()
+      / (n - 1) as f32
+  } else {
+    0.0
+  };
+  let total_seconds: f64 = runs.iter().map(|r| r.seconds).sum();
+  let total_evaluations: usize = runs.iter().map(|r| r.evaluations).sum();
+
+  CaseSummary {
+    case: runs[0].case.clone(),
+    algorithm: runs[0].algorithm,
+    metric: runs[0].metric,
+    target: target.to_string(),
+    runs: n,
+    mean_accuracy,
+    std_accuracy: variance.sqrt(),
+    mean_evaluations: total_evaluations as f64 / n as f64,
+    mean_seconds: total_seconds / n as f64,
+    evaluations_per_second: total_evaluations as f64 / total_seconds.max(f64::EPSILON),
+  }
+}
+
+/// Per case and target summaries, plus an "all" row per case when there
+/// is more than one target. Cases keep the order they were run in.
+pub fn summarise(results: &[RunResult]) -> Vec<CaseSummary> {
+  let mut cases: Vec<&str> = Vec::new();
+  for result in results {
+    if !cases.contains(&result.case.as_str()) {
+      cases.push(&result.case);
+    }
+  }
+
+  let mut summaries = Vec::new();
+  for case in cases {
+    let case_runs: Vec<&RunResult> = results.iter().filter(|r| r.case == case).collect();
+
+    let mut targets: Vec<&str> = Vec::new();
+    for run in &case_runs {
+      if !targets.contains(&run.target.as_str()) {
+        targets.push(&run.target);
+      }
+    }
+
+    for target in &targets {
+      let runs: Vec<&RunResult> = case_runs
+        .iter()
+        .copied()
+        .filter(|r| r.target == *target)
+        .collect();
+      summaries.push(summarise_runs(&runs, target));
+    }
+    if targets.len() > 1 {
+      summaries.push(summarise_runs(&case_runs, "all"));
+    }
+  }
+
+  summaries
+}
+
+/// A note on runs that went over `budget`, which is only checked between
+/// iterations
+pub fn budget_note(results: &[RunResult], budget: usize) -> Option<String> {
+  let over: Vec<&RunResult> = results.iter().filter(|r| r.evaluations > budget).collect();
+  let worst = over.iter().map(|r| r.evaluations - budget).max()?;
+  Some(format!(
+    "{} of {} runs exceeded the {}-evaluation budget by up to {}, as it is checked between iterations",
+    over.len(),
+    results.len(),
+    budget,
+    worst
+  ))
+}
+
+/// Fixed-width table for the terminal
+pub fn format_table(summaries: &[CaseSummary]) -> String {
+  let case_width = summaries
+    .iter()
+    .map(|s| s.case.len())
+    .chain(std::iter::once(4))
+    .max()
+    .unwrap();
+  let target_width = summaries
+    .iter()
+    .map(|s| s.target.len())
+    .chain(std::iter::once(6))
+    .max()
+    .unwrap();
+
+  let mut table = format!(
+    "{:<cw$}  {:<tw$}  {:>4}  {:>9}  {:>8}  {:>9}  {:>9}  {:>10}\n",
+    "case",
+    "target",
+    "runs",
+    "accuracy",
+    "stddev",
+    "evals",
+    "time (s)",
+    "evals/s",
+    cw = case_width,
+    tw = target_width
+  );
+  for s in summaries {
+    table.push_str(&format!(
+      "{:<cw$}  {:<tw$}  {:>4}  {:>8.4}%  {:>7.4}%  {:>9.0}  {:>9.2}  {:>10.0}\n",
+      s.case,
+      s.target,
+      s.runs,
+      s.mean_accuracy * 100.0,
+      s.std_accuracy * 100.0,
+      s.mean_evaluations,
+      s.mean_seconds,
+      s.evaluations_per_second,
+      cw = case_width,
+      tw = target_width
+    ));
+  }
+  table
+}
+
+fn csv_field(value: &str) -> String {
+  if value.contains([',', '"', '\n']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value.to_string()
+  }
+}
+
+pub fn summaries_to_csv(summaries: &[CaseSummary]) -> String {
+  let mut csv = String::from(
+    "case,algorithm,metric,target,runs,mean_accuracy,std_accuracy,mean_evaluations,mean_seconds,evaluations_per_second\n",
+  );
+  for s in summaries {
+    csv.push_str(&format!(
+      "{},{},{},{},{},{},{},{},{},{}\n",
+      csv_field(&s.case),
+      s.algorithm.name(),
+      s.metric.name(),
+      csv_field(&s.target),
+      s.runs,
+      s.mean_accuracy,
+      s.std_accuracy,
+      s.mean_evaluations,
+      s.mean_seconds,
+      s.evaluations_per_second
+    ));
+  }
+  csv
+}
+
+fn json_string(value: &str) -> String {
+  let mut out = String::from("\"");
+  for c in value.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
+
+/// Summaries and individual runs as a JSON document
+pub fn to_json(summaries: &[CaseSummary], runs: &[RunResult]) -> String {
+  let summary_entries: Vec<String> = summaries
+    .iter()
+    .map(|s| {
+      format!(
+        "    {{\"case\": {}, \"algorithm\": \"{}\", \"metric\": \"{}\", \"target\": {}, \"runs\": {}, \"mean_accuracy\": {}, \"std_accuracy\": {}, \"mean_evaluations\": {}, \"mean_seconds\": {}, \"evaluations_per_second\": {}}}",
+        json_string(&s.case),
+        s.algorithm.name(),
+        s.metric.name(),
+        json_string(&s.target),
+        s.runs,
+        s.mean_accuracy,
+        s.std_accuracy,
+        s.mean_evaluations,
+        s.mean_seconds,
+        s.evaluations_per_second
+      )
+    })
+    .collect();
+
+  let run_entries: Vec<String> = runs
+    .iter()
+    .map(|r| {
+      format!(
+        "    {{\"case\": {}, \"algorithm\": \"{}\", \"metric\": \"{}\", \"target\": {}, \"seed\": {}, \"accuracy\": {}, \"evaluations\": {}, \"iterations\": {}, \"seconds\": {}}}",
+        json_string(&r.case),
+        r.algorithm.name(),
+        r.metric.name(),
+        json_string(&r.target),
+        r.seed,
+        r.accuracy,
+        r.evaluations,
+        r.iterations,
+        r.seconds
+      )
+    })
+    .collect();
+
+  format!(
+    "{{\n  \"summaries\": [\n{}\n  ],\n  \"runs\": [\n{}\n  ]\n}}\n",
+    summary_entries.join(",\n"),
+    run_entries.join(",\n")
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn gradient_target() -> BenchmarkTarget {
+    let (width, height) = (16, 12);
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+      for x in 0..width {
+        pixels.extend_from_slice(&[(x * 16) as u8, (y * 20) as u8, 128, 255]);
+      }
+    }
+    BenchmarkTarget {
+      name: "gradient".to_string(),
+      pixels,
+      width,
+      height,
+    }
+  }
+
+  fn case(algorithm: BenchmarkAlgorithm) -> BenchmarkCase {
+    BenchmarkCase {
+      label: algorithm.name().to_string(),
+      algorithm,
+      config: AlgorithmConfig {
+        // Out of reach, so only the budget stops a run
+        target_accuracy: 2.0,
+        ..AlgorithmConfig::default()
+      },
+    }
+  }
+
+  fn benchmark(cases: Vec<BenchmarkCase>, evaluation_budget: usize) -> BenchmarkConfig {
+    BenchmarkConfig {
+      cases,
+      targets: vec![gradient_target()],
+      seeds: vec![0, 1],
+      evaluation_budget,
+    }
+  }
+
+  #[test]
+  fn same_seeds_give_the_same_rows() {
+    let config = benchmark(
+      vec![
+        case(BenchmarkAlgorithm::Strongest),
+        case(BenchmarkAlgorithm::Shared(AlgorithmType::EvolutionStrategy)),
+        case(BenchmarkAlgorithm::Shared(AlgorithmType::GeneticAlgorithm)),
+      ],
+      200,
+    );
+    // Everything but wall time
+    let rows = |results: Vec<RunResult>| {
+      results
+        .into_iter()
+        .map(|r| (r.case, r.seed, r.accuracy, r.evaluations, r.iterations))
+        .collect::<Vec<_>>()
+    };
+
+    let first = rows(run_benchmark(&config, |_| {}));
+    assert_eq!(first.len(), 6);
+    assert_eq!(first, rows(run_benchmark(&config, |_| {})));
+  }
+
+  #[test]
+  fn runs_stop_within_one_iteration_of_the_budget() {
+    let budget = 300;
+    // Most evaluations one iteration can add: 3 ES parents with 5
+    // children each plus the wildcard, a GA generation, and 3 strongest
+    // parents with 10 children each plus the parents themselves when
+    // unscored
+    let cases = [
+      (BenchmarkAlgorithm::Strongest, 33),
+      (BenchmarkAlgorithm::Shared(AlgorithmType::EvolutionStrategy), 16),
+      (BenchmarkAlgorithm::Shared(AlgorithmType::GeneticAlgorithm), 30),
+    ];
+    let config = benchmark(cases.iter().map(|&(algorithm, _)| case(algorithm)).collect(), budget);
+
+    for result in run_benchmark(&config, |_| {}) {
+      let per_iteration = cases.iter().find(|(a, _)| *a == result.algorithm).unwrap().1;
+      assert!(result.evaluations >= budget, "{} stopped early", result.case);
+      assert!(
+        result.evaluations < budget + per_iteration,
+        "{} used {} evaluations",
+        result.case,
+        result.evaluations
+      );
+    }
+  }
+}
@@ -0,0 +1,587 @@
+use crate::encoding::Topology;
+use crate::random::standard_normal;
+use crate::types::Image;
+
+/// Covariance model adapted by CMA-ES
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Covariance {
+  /// Full covariance matrix; learns correlations between genes, O(n^2) memory
+  Full,
+  /// Diagonal only (sep-CMA-ES); linear cost, suited to large genomes
+  Diagonal,
+}
+
+impl Covariance {
+  pub fn from_name(name: &str) -> Option<Covariance> {
+    match name {
+      "full" => Some(Covariance::Full),
+      "diagonal" => Some(Covariance::Diagonal),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct CmaesConfig {
+  /// Initial step size in normalised genome units (see `encoding::Topology`)
+  pub sigma: f32,
+  /// Samples per generation; 0 uses the standard 4 + 3 ln(n)
+  pub population_size: usize,
+  pub covariance: Covariance,
+}
+
+impl Default for CmaesConfig {
+  fn default() -> Self {
+    Self {
+      sigma: 0.05,
+      population_size: 0,
+      covariance: Covariance::Diagonal,
+    }
+  }
+}
+
+/// CMA-ES over the flattened genome of a fixed-topology `Image`
+pub struct Cmaes {
+  topology: Topology,
+  n: usize,
+  lambda: usize,
+  weights: Vec<f64>,
+  mueff: f64,
+  cc: f64,
+  cs: f64,
+  c1: f64,
+  cmu: f64,
+  damps: f64,
+  chi_n: f64,
+  covariance: Covariance,
+  mean: Vec<f64>,
+  sigma: f64,
+  pc: Vec<f64>,
+  ps: Vec<f64>,
+  /// Full: n x n row-major covariance; Diagonal: its n diagonal entries
+  c: Vec<f64>,
+  /// Eigenvectors of C (full only), row-major with vectors in columns
+  b: Vec<f64>,
+  /// Square roots of the eigenvalues of C
+  d: Vec<f64>,
+  generation: usize,
+  eigen_generation: usize,
+  best: (f32, Image),
+}
+
+impl Cmaes {
+  /// Start a search centred on `initial`, which also fixes the topology.
+  /// `initial_score` is its score, used as the best-so-far baseline.
+  pub fn new(initial: &Image, initial_score: f32, config: &CmaesConfig) -> Self {
+    let topology = Topology::of(initial);
+    let n = topology.dimension().max(1);
+    let nf = n as f64;
+
+    let lambda = if config.population_size > 0 {
+      config.population_size.max(2)
+    } else {
+      4 + (3.0 * nf.ln()).floor() as usize
+    };
+    let mu = lambda / 2;
+    let raw: Vec<f64> = (0..mu)
+      .map(|i| (mu as f64 + 0.5).ln() - ((i + 1) as f64).ln())
+      .collect();
+    let sum: f64 = raw.iter().sum();
+    let weights: Vec<f64> = raw.iter().map(|w| w / sum).collect();
+    let mueff = 1.0 / weights.iter().map(|w| w * w).sum::<f64>();
+
+    let cc = (4.0 + mueff / nf) / (nf + 4.0 + 2.0 * mueff / nf);
+    let cs = (mueff + 2.0) / (nf + mueff + 5.0);
+    let mut c1 = 2.0 / ((nf + 1.3).powi(2) + mueff);
+    let mut cmu = (2.0 * (mueff - 2.0 + 1.0 / mueff) / ((nf + 2.0).powi(2) + mueff)).min(1.0 - c1);
+    if config.covariance == Covariance::Diagonal {
+      // sep-CMA-ES learning rates (Ros & Hansen 2008)
+      c1 = (c1 * (nf + 2.0) / 3.0).min(1.0);
+      cmu = (cmu * (nf + 2.0) / 3.0).min(1.0 - c1);
+    }
+    let damps = 1.0 + 2.0 * (((mueff - 1.0) / (nf + 1.0)).sqrt() - 1.0).max(0.0) + cs;
+    let chi_n = nf.sqrt() * (1.0 - 1.0 / (4.0 * nf) + 1.0 / (21.0 * nf * nf));
+
+    let (c, b) = match config.covariance {
+      Covariance::Full => (identity(n), identity(n)),
+      Covariance::Diagonal => (vec![1.0; n], Vec::new()),
+    };
+
+    let mut mean: Vec<f64> = topology.encode(initial).iter().map(|v| *v as f64).collect();
+    mean.resize(n, 0.5);
+
+    Self {
+      topology,
+      n,
+      lambda,
+      weights,
+      mueff,
+      cc,
+      cs,
+      c1,
+      cmu,
+      damps,
+      chi_n,
+      covariance: config.covariance,
+      mean,
+      sigma: config.sigma as f64,
+      pc: vec![0.0; n],
+      ps: vec![0.0; n],
+      c,
+      b,
+      d: vec![1.0; n],
+      generation: 0,
+      eigen_generation: 0,
+      best: (initial_score, initial.clone()),
+    }
+  }
+
+  pub fn topology(&self) -> &Topology {
+    &self.topology
+  }
+
+  pub fn population_size(&self) -> usize {
+    self.lambda
+  }
+
+  pub fn sigma(&self) -> f32 {
+    self.sigma as f32
+  }
+
+  pub fn best(&self) -> (f32, &Image) {
+    (self.best.0, &self.best.1)
+  }
+
+  /// Move the search to a better genome found elsewhere (e.g. a migrant).
+  /// Ignored unless it has this topology and beats the current best.
+  pub fn recentre(&mut self, image: &Image, score: f32) {
+    if score <= self.best.0 || !self.topology.matches(image) {
+      return;
+    }
+    self.mean = self.topology.encode(image).iter().map(|v| *v as f64).collect();
+    self.best = (score, image.clone());
+  }
+
+  /// Decode the current distribution mean into an image
+  pub fn mean_image(&self) -> Image {
+    let genome: Vec<f32> = self.mean.iter().map(|v| *v as f32).collect();
+    self.topology.decode(&genome)
+  }
+
+  /// Run one generation: sample, score each decoded sample with `evaluate`
+  /// (higher is better) and update the distribution
+  pub fn step<F: FnMut(&Image) -> f32>(&mut self, mut evaluate: F) {
+    let n = self.n;
+
+    // Sample y = B D z and x = m + sigma y
+    let mut samples: Vec<(f32, Vec<f64>, Image)> = (0..self.lambda)
+      .map(|_| {
+        let z: Vec<f64> = (0..n).map(|_| standard_normal() as f64).collect();
+        let y = self.transform(&z);
+        let x: Vec<f64> = (0..n).map(|i| self.mean[i] + self.sigma * y[i]).collect();
+        let genome: Vec<f32> = x.iter().map(|v| *v as f32).collect();
+        let image = self.topology.decode(&genome);
+        let score = evaluate(&image);
+        (score, y, image)
+      })
+      .collect();
+
+    samples.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    if samples[0].0 > self.best.0 {
+      self.best = (samples[0].0, samples[0].2.clone());
+    }
+
+    // Weighted recombination of the best mu steps
+    let mut y_w = vec![0.0; n];
+    for (w, (_, y, _)) in self.weights.iter().zip(&samples) {
+      for (acc, yi) in y_w.iter_mut().zip(y) {
+        *acc += w * yi;
+      }
+    }
+    for (m, yi) in self.mean.iter_mut().zip(&y_w) {
+      *m = (*m + self.sigma * yi).clamp(0.0, 1.0);
+    }
+
+    // Step-size path uses C^-1/2 y_w
+    let inv_sqrt_y = self.inverse_sqrt_transform(&y_w);
+    let cs_norm = (self.cs * (2.0 - self.cs) * self.mueff).sqrt();
+    for (p, yi) in self.ps.iter_mut().zip(&inv_sqrt_y) {
+      *p = (1.0 - self.cs) * *p + cs_norm * yi;
+    }
+    let ps_norm = self.ps.iter().map(|v| v * v).sum::<f64>().sqrt();
+
+    self.generation += 1;
+    let decay = 1.0 - (1.0 - self.cs).powi(2 * self.generation as i32);
+    let hsig = ps_norm / decay.sqrt() / self.chi_n < 1.4 + 2.0 / (n as f64 + 1.0);
+    let hsig_f = if hsig { 1.0 } else { 0.0 };
+
+    let cc_norm = (self.cc * (2.0 - self.cc) * self.mueff).sqrt();
+    for (p, yi) in self.pc.iter_mut().zip(&y_w) {
+      *p = (1.0 - self.cc) * *p + hsig_f * cc_norm * yi;
+    }
+
+    // Covariance update: rank-one plus rank-mu
+    let old_weight = 1.0 - self.c1 - self.cmu + (1.0 - hsig_f) * self.c1 * self.cc * (2.0 - self.cc);
+    match self.covariance {
+      Covariance::Diagonal => {
+        for i in 0..n {
+          let rank_mu: f64 = self
+            .weights
+            .iter()
+            .zip(&samples)
+            .map(|(w, (_, y, _))| w * y[i] * y[i])
+            .sum();
+          self.c[i] = old_weight * self.c[i] + self.c1 * self.pc[i] * self.pc[i] + self.cmu * rank_mu;
+        }
+      }
+      Covariance::Full => {
+        for i in 0..n {
+          for j in 0..=i {
+            let rank_mu: f64 = self
+              .weights
+              .iter()
+              .zip(&samples)
+              .map(|(w, (_, y, _))| w * y[i] * y[j])
+              .sum();
+            let value = old_weight * self.c[i * n + j]
+              + self.c1 * self.pc[i] * self.pc[j]
+              + self.cmu * rank_mu;
+            self.c[i * n + j] = value;
+            self.c[j * n + i] = value;
+          }
+        }
+      }
+    }
+
+    self.sigma *= ((self.cs / self.damps) * (ps_norm / self.chi_n - 1.0)).exp();
+    self.update_eigensystem();
+  }
+
+  /// y = B D z (or D z for the diagonal model)
+  fn transform(&self, z: &[f64]) -> Vec<f64> {
+    let n = self.n;
+    match self.covariance {
+      Covariance::Diagonal => (0..n).map(|i| self.d[i] * z[i]).collect(),
+      Covariance::Full => {
+        let dz: Vec<f64> = (0..n).map(|j| self.d[j] * z[j]).collect();
+        (0..n)
+          .map(|i| (0..n).map(|j| self.b[i * n + j] * dz[j]).sum())
+          .collect()
+      }
+    }
+  }
+
+  /// C^-1/2 y = B D^-1 B^T y (or D^-1 y for the diagonal model)
+  fn inverse_sqrt_transform(&self, y: &[f64]) -> Vec<f64> {
+    let n = self.n;
+    match self.covariance {
+      Covariance::Diagonal => (0..n).map(|i| y[i] / self.d[i]).collect(),
+      Covariance::Full => {
+        let bty: Vec<f64> = (0..n)
+          .map(|j| (0..n).map(|i| self.b[i * n + j] * y[i]).sum::<f64>() / self.d[j])
+          .collect();
+        (0..n)
+          .map(|i| (0..n).map(|j| self.b[i * n + j] * bty[j]).sum())
+          .collect()
+      }
+    }
+  }
+
+  fn update_eigensystem(&mut self) {
+    match self.covariance {
+      Covariance::Diagonal => {
+        self.d = self.c.iter().map(|v| v.max(1e-20).sqrt()).collect();
+      }
+      Covariance::Full => {
+        // The decomposition is O(n^3), so only refresh it as often as C
+        // meaningfully changes
+        let n = self.n as f64;
+        let interval = (1.0 / ((self.c1 + self.cmu) * n * 10.0)).max(1.0) as usize;
+        if self.generation - self.eigen_generation < interval {
+          return;
+        }
+        self.eigen_generation = self.generation;
+
+        let (values, vectors) = symmetric_eigen(&self.c, self.n);
+        self.d = values.iter().map(|v| v.max(1e-20).sqrt()).collect();
+        self.b = vectors;
+      }
+    }
+  }
+}
+
+/// Refine the geometry and colours of `image` with CMA-ES, keeping its topology.
+/// Returns the best score and genome found, never worse than the input.
+pub fn refine_with_cmaes<F: FnMut(&Image) -> f32>(
+  image: &Image,
+  config: &CmaesConfig,
+  generations: usize,
+  mut evaluate: F,
+) -> (f32, Image) {
+  let initial_score = evaluate(image);
+  let mut cmaes = Cmaes::new(image, initial_score, config);
+  for _ in 0..generations {
+    cmaes.step(&mut evaluate);
+  }
+  let (score, best) = cmaes.best();
+  let mut best = best.clone();
+  best.step_size = image.step_size;
+  (score, best)
+}
+
+fn identity(n: usize) -> Vec<f64> {
+  let mut m = vec![0.0; n * n];
+  for i in 0..n {
+    m[i * n + i] = 1.0;
+  }
+  m
+}
+
+/// Eigen decomposition of a symmetric row-major n x n matrix by Householder
+/// tridiagonalisation and the QL algorithm (tred2/tql2 from EISPACK via JAMA).
+/// Returns the eigenvalues and the eigenvectors as columns of a row-major matrix.
+// Kept index-based to stay line-for-line comparable with the reference code
+#[allow(clippy::needless_range_loop, clippy::manual_memcpy)]
+fn symmetric_eigen(matrix: &[f64], n: usize) -> (Vec<f64>, Vec<f64>) {
+  let mut v: Vec<Vec<f64>> = (0..n).map(|i| matrix[i * n..(i + 1) * n].to_vec()).collect();
+  let mut d = vec![0.0; n];
+  let mut e = vec![0.0; n];
+
+  // tred2: reduce to tridiagonal form
+  for j in 0..n {
+    d[j] = v[n - 1][j];
+  }
+  for i in (1..n).rev() {
+    let mut scale = 0.0;
+    let mut h = 0.0;
+    for k in 0..i {
+      scale += d[k].abs();
+    }
+    if scale == 0.0 {
+      e[i] = d[i - 1];
+      for j in 0..i {
+        d[j] = v[i - 1][j];
+        v[i][j] = 0.0;
+        v[j][i] = 0.0;
+      }
+    } else {
+      for k in 0..i {
+        d[k] /= scale;
+        h += d[k] * d[k];
+      }
+      let mut f = d[i - 1];
+      let mut g = h.sqrt();
+      if f > 0.0 {
+        g = -g;
+      }
+      e[i] = scale * g;
+      h -= f * g;
+      d[i - 1] = f - g;
+      for item in e.iter_mut().take(i) {
+        *item = 0.0;
+      }
+      for j in 0..i {
+        f = d[j];
+        v[j][i] = f;
+        g = e[j] + v[j][j] * f;
+        for k in (j + 1)..i {
+          g += v[k][j] * d[k];
+          e[k] += v[k][j] * f;
+        }
+        e[j] = g;
+      }
+      f = 0.0;
+      for j in 0..i {
+        e[j] /= h;
+        f += e[j] * d[j];
+      }
+      let hh = f / (h + h);
+      for j in 0..i {
+        e[j] -= hh * d[j];
+      }
+      for j in 0..i {
+        f = d[j];
+        g = e[j];
+        for k in j..i {
+          v[k][j] -= f * e[k] + g * d[k];
+        }
+        d[j] = v[i - 1][j];
+        v[i][j] = 0.0;
+      }
+    }
+    d[i] = h;
+  }
+
+  for i in 0..n.saturating_sub(1) {
+    v[n - 1][i] = v[i][i];
+    v[i][i] = 1.0;
+    let h = d[i + 1];
+    if h != 0.0 {
+      for k in 0..=i {
+        d[k] = v[k][i + 1] / h;
+      }
+      for j in 0..=i {
+        let mut g = 0.0;
+        for k in 0..=i {
+          g += v[k][i + 1] * v[k][j];
+        }
+        for k in 0..=i {
+          v[k][j] -= g * d[k];
+        }
+      }
+    }
+    for k in 0..=i {
+      v[k][i + 1] = 0.0;
+    }
+  }
+  for j in 0..n {
+    d[j] = v[n - 1][j];
+    v[n - 1][j] = 0.0;
+  }
+  if n > 0 {
+    v[n - 1][n - 1] = 1.0;
+  }
+  e[0] = 0.0;
+
+  // tql2: diagonalise the tridiagonal matrix
+  for i in 1..n {
+    e[i - 1] = e[i];
+  }
+  if n > 0 {
+    e[n - 1] = 0.0;
+  }
+
+  let mut f = 0.0;
+  let mut tst1: f64 = 0.0;
+  let eps = f64::EPSILON;
+  for l in 0..n {
+    tst1 = tst1.max(d[l].abs() + e[l].abs());
+    let mut m = l;
+    while m < n {
+      if e[m].abs() <= eps * tst1 {
+        break;
+      }
+      m += 1;
+    }
+    let m = m.min(n - 1);
+
+    if m > l {
+      loop {
+        let mut g = d[l];
+        let mut p = (d[l + 1] - g) / (2.0 * e[l]);
+        let mut r = p.hypot(1.0);
+        if p < 0.0 {
+          r = -r;
+        }
+        d[l] = e[l] / (p + r);
+        d[l + 1] = e[l] * (p + r);
+        let dl1 = d[l + 1];
+        let mut h = g - d[l];
+        for item in d.iter_mut().take(n).skip(l + 2) {
+          *item -= h;
+        }
+        f += h;
+
+        p = d[m];
+        let mut c = 1.0;
+        let mut c2 = c;
+        let mut c3 = c;
+        let el1 = e[l + 1];
+        let mut s = 0.0;
+        let mut s2 = 0.0;
+        for i in (l..m).rev() {
+          c3 = c2;
+          c2 = c;
+          s2 = s;
+          g = c * e[i];
+          h = c * p;
+          r = p.hypot(e[i]);
+          e[i + 1] = s * r;
+          s = e[i] / r;
+          c = p / r;
+          p = c * d[i] - s * g;
+          d[i + 1] = h + s * (c * g + s * d[i]);
+          for row in v.iter_mut() {
+            h = row[i + 1];
+            row[i + 1] = s * row[i] + c * h;
+            row[i] = c * row[i] - s * h;
+          }
+        }
+        p = -s * s2 * c3 * el1 * e[l] / dl1;
+        e[l] = s * p;
+        d[l] = c * p;
+
+        if e[l].abs() <= eps * tst1 {
+          break;
+        }
+      }
+    }
+    d[l] += f;
+    e[l] = 0.0;
+  }
+
+  let vectors = v.into_iter().flatten().collect();
+  (d, vectors)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// `V diag(values) V^T` for eigenvectors stored as the columns of `v`
+  fn reconstruct(values: &[f64], v: &[f64], n: usize) -> Vec<f64> {
+    let mut m = vec![0.0; n * n];
+    for i in 0..n {
+      for j in 0..n {
+        m[i * n + j] = (0..n).map(|k| v[i * n + k] * values[k] * v[j * n + k]).sum();
+      }
+    }
+    m
+  }
+
+  fn assert_close(a: &[f64], b: &[f64]) {
+    for (x, y) in a.iter().zip(b) {
+      assert!((x - y).abs() < 1e-9, "{:?} != {:?}", a, b);
+    }
+  }
+
+  #[test]
+  fn eigen_of_diagonal_matrix() {
+    let matrix = [3.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 2.0];
+    let (values, vectors) = symmetric_eigen(&matrix, 3);
+    let mut sorted = values.clone();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_close(&sorted, &[1.0, 2.0, 3.0]);
+    assert_close(&reconstruct(&values, &vectors, 3), &matrix);
+  }
+
+  #[test]
+  fn eigen_of_rotated_matrix() {
+    // diag(1, 3) rotated by 45 degrees
+    let matrix = [2.0, 1.0, 1.0, 2.0];
+    let (values, vectors) = symmetric_eigen(&matrix, 2);
+    let half = std::f64::consts::FRAC_1_SQRT_2;
+    for (k, &value) in values.iter().enumerate() {
+      let (x, y) = (vectors[k], vectors[2 + k]);
+      let expected = if (value - 1.0).abs() < 1e-9 {
+        (half, -half)
+      } else {
+        assert!((value - 3.0).abs() < 1e-9, "unexpected eigenvalue {}", value);
+        (half, half)
+      };
+      // Eigenvectors are unique up to sign
+      let dot = x * expected.0 + y * expected.1;
+      assert!((dot.abs() - 1.0).abs() < 1e-9);
+    }
+    assert_close(&reconstruct(&values, &vectors, 2), &matrix);
+  }
+
+  #[test]
+  fn eigen_reconstructs_dense_matrix() {
+    let matrix = [4.0, 1.0, -2.0, 0.5, 1.0, 3.0, 0.0, 1.5, -2.0, 0.0, 5.0, -1.0, 0.5, 1.5, -1.0, 2.0];
+    let (values, vectors) = symmetric_eigen(&matrix, 4);
+    assert_close(&reconstruct(&values, &vectors, 4), &matrix);
+    // The eigenvectors are orthonormal
+    assert_close(&reconstruct(&[1.0; 4], &vectors, 4), &identity(4));
+  }
+}
@@ -0,0 +1,205 @@
+use crate::renderer_wasm::{blend_channel, polygon_coverage, render_image};
+use crate::types::Image;
+
+/// Furthest from the least-squares value a channel is searched for the one
+/// that best reproduces the target once the renderer truncates each blend
+const MAX_REFINE: i32 = 4;
+
+/// Pixels one polygon covers, with how much of its colour reaches the final
+/// image at each: its alpha times the transmittance of everything above it
+struct Footprint {
+  pixels: Vec<usize>,
+  weights: Vec<f32>,
+}
+
+/// Footprints of the polygons in `indices`, found in one pass from the top
+/// polygon down so the transmittance above each is known when it is reached
+fn footprints(image: &Image, indices: &[usize]) -> Vec<Option<Footprint>> {
+  let (width, height) = (image.width, image.height);
+  let mut transmittance = vec![1.0f32; width * height];
+  let mut result: Vec<Option<Footprint>> = (0..image.polygon.len()).map(|_| None).collect();
+  let lowest = indices.iter().copied().min().unwrap_or(image.polygon.len());
+
+  for (i, polygon) in image.polygon.iter().enumerate().skip(lowest).rev() {
+    let pixels = polygon_coverage(polygon, width, height);
+    let alpha = polygon.colour[3] as f32 / 255.0;
+
+    let weights: Vec<f32> = if indices.contains(&i) {
+      pixels.iter().map(|&p| alpha * transmittance[p]).collect()
+    } else {
+      Vec::new()
+    };
+    for &p in &pixels {
+      transmittance[p] *= 1.0 - alpha;
+    }
+    if indices.contains(&i) {
+      result[i] = Some(Footprint { pixels, weights });
+    }
+  }
+
+  result
+}
+
+/// Polygons covering each pixel, bottom to top
+fn layers(image: &Image) -> Vec<Vec<usize>> {
+  let mut layers = vec![Vec::new(); image.width * image.height];
+  for (i, polygon) in image.polygon.iter().enumerate() {
+    for p in polygon_coverage(polygon, image.width, image.height) {
+      layers[p].push(i);
+    }
+  }
+  layers
+}
+
+/// A pixel's channel as the renderer produces it, with polygon `index`
+/// painted in `value` instead of its own colour
+fn composite(image: &Image, layers: &[usize], channel: usize, index: usize, value: u8) -> u8 {
+  layers.iter().fold(0, |below, &i| {
+    let colour = image.polygon[i].colour;
+    let own = if i == index { value } else { colour[channel] };
+    blend_channel(own, colour[3] as f32 / 255.0, below)
+  })
+}
+
+/// Move one polygon's RGB to the least-squares optimum against the target,
+/// holding its alpha, its geometry and every other polygon fixed. The final
+/// colour at a covered pixel is linear in the polygon's colour, so the
+/// optimum is closed form up to the renderer's 8-bit truncation, which the
+/// values around it are then composited exactly to account for. `rendered`
+/// is the current render as floats and is updated in place.
+fn solve_one(
+  image: &mut Image,
+  index: usize,
+  footprint: &Footprint,
+  layers: &[Vec<usize>],
+  target: &[u8],
+  rendered: &mut [f32],
+) {
+  let denominator: f32 = footprint.weights.iter().map(|w| w * w).sum();
+  if denominator <= f32::EPSILON {
+    return;
+  }
+
+  // The estimate can be off by about one level over the polygon's alpha
+  let alpha = image.polygon[index].colour[3] as f32 / 255.0;
+  let radius = ((1.0 / alpha).ceil() as i32).clamp(1, MAX_REFINE);
+
+  for channel in 0..3 {
+    let numerator: f32 = footprint
+      .pixels
+      .iter()
+      .zip(&footprint.weights)
+      .map(|(&p, w)| w * (target[p * 4 + channel] as f32 - rendered[p * 4 + channel]))
+      .sum();
+
+    let old = image.polygon[index].colour[channel] as f32;
+    let estimate = (old + numerator / denominator).round().clamp(0.0, 255.0) as i32;
+
+    let error = |value: u8| -> u64 {
+      footprint
+        .pixels
+        .iter()
+        .map(|&p| {
+          let value = composite(image, &layers[p], channel, index, value);
+          let diff = value as i64 - target[p * 4 + channel] as i64;
+          (diff * diff) as u64
+        })
+        .sum()
+    };
+    // Nearest the estimate wins ties
+    let mut candidates: Vec<u8> = ((estimate - radius).max(0)..=(estimate + radius).min(255))
+      .map(|v| v as u8)
+      .collect();
+    candidates.sort_by_key(|&v| (v as i32 - estimate).abs());
+    let best = candidates.into_iter().min_by_key(|&v| error(v)).unwrap();
+
+    image.polygon[index].colour[channel] = best;
+    for &p in &footprint.pixels {
+      rendered[p * 4 + channel] = composite(image, &layers[p], channel, index, best) as f32;
+    }
+  }
+}
+
+fn rendered_floats(image: &Image) -> Vec<f32> {
+  render_image(image).data.iter().map(|&v| v as f32).collect()
+}
+
+/// Set one polygon's RGB to its optimum for the current geometry
+pub fn solve_polygon_colour(mut image: Image, index: usize, target: &[u8]) -> Image {
+  if index >= image.polygon.len() || target.len() != image.width * image.height * 4 {
+    return image;
+  }
+
+  let mut rendered = rendered_floats(&image);
+  if let Some(footprint) = footprints(&image, &[index]).swap_remove(index) {
+    let layers = layers(&image);
+    solve_one(&mut image, index, &footprint, &layers, target, &mut rendered);
+  }
+  image
+}
+
+/// Jointly least-squares fit every polygon's RGB for the current geometry
+/// by coordinate descent: each sweep solves the polygons bottom to top,
+/// each against the others' latest colours. Alphas are left unchanged.
+pub fn solve_all_colours(mut image: Image, target: &[u8], sweeps: usize) -> Image {
+  if target.len() != image.width * image.height * 4 {
+    return image;
+  }
+
+  let indices: Vec<usize> = (0..image.polygon.len()).collect();
+  let footprints = footprints(&image, &indices);
+  let layers = layers(&image);
+  let mut rendered = rendered_floats(&image);
+
+  for _ in 0..sweeps {
+    for (index, footprint) in footprints.iter().enumerate() {
+      if let Some(footprint) = footprint {
+        solve_one(&mut image, index, footprint, &layers, target, &mut rendered);
+      }
+    }
+  }
+  image
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::Polygon;
+
+  /// An opaque background colour under a triangle of `colour`
+  fn image(colour: [u8; 4]) -> Image {
+    Image {
+      polygon: vec![
+        Polygon {
+          points: vec![(0.0, 0.0), (20.0, 0.0), (20.0, 16.0), (0.0, 16.0)],
+          colour: [30, 60, 90, 255],
+        },
+        Polygon {
+          points: vec![(2.0, 2.0), (18.0, 4.0), (6.0, 14.0)],
+          colour,
+        },
+      ],
+      width: 20,
+      height: 16,
+      step_size: 1.0,
+    }
+  }
+
+  #[test]
+  fn solves_a_colour_that_reproduces_a_blended_target() {
+    for alpha in [255, 128, 77] {
+      let colour = [200, 40, 120, alpha];
+      let target = render_image(&image(colour)).data;
+
+      let solved = solve_polygon_colour(image([0, 255, 0, alpha]), 1, &target);
+      assert_eq!(render_image(&solved).data, target, "alpha {}", alpha);
+      assert_eq!(solved.polygon[0].colour, [30, 60, 90, 255]);
+      // Truncation maps a few neighbouring colours to the same pixels
+      let tolerance = (255.0 / alpha as f32).floor() as i32;
+      for (&got, &expected) in solved.polygon[1].colour.iter().zip(&colour).take(3) {
+        assert!((got as i32 - expected as i32).abs() <= tolerance);
+      }
+      assert_eq!(solved.polygon[1].colour[3], alpha);
+    }
+  }
+}
@@ -0,0 +1,266 @@
+use crate::types::Image;
+
+// 2-opt passes before falling back to an angular ordering
+const MAX_UNTANGLE_PASSES: usize = 64;
+
+/// Shape every polygon must keep
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolygonShape {
+  /// No restriction; self-intersecting polygons render as bow-ties
+  Any,
+  /// No two edges cross
+  Simple,
+  /// Simple with every turn in the same direction
+  Convex,
+}
+
+impl PolygonShape {
+  pub fn name(self) -> &'static str {
+    match self {
+      PolygonShape::Any => "any",
+      PolygonShape::Simple => "simple",
+      PolygonShape::Convex => "convex",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<PolygonShape> {
+    match name {
+      "any" => Some(PolygonShape::Any),
+      "simple" => Some(PolygonShape::Simple),
+      "convex" => Some(PolygonShape::Convex),
+      _ => None,
+    }
+  }
+}
+
+/// Validity rules mutations keep and `repair` restores
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PolygonConstraints {
+  pub shape: PolygonShape,
+  /// Vertex limit per polygon, at least 3
+  pub max_vertices: Option<usize>,
+}
+
+impl Default for PolygonConstraints {
+  fn default() -> Self {
+    Self {
+      shape: PolygonShape::Any,
+      max_vertices: None,
+    }
+  }
+}
+
+impl PolygonConstraints {
+  pub fn is_active(&self) -> bool {
+    self.shape != PolygonShape::Any || self.max_vertices.is_some()
+  }
+
+  pub fn allows(&self, points: &[(f32, f32)]) -> bool {
+    if self.max_vertices.is_some_and(|max| points.len() > max.max(3)) {
+      return false;
+    }
+    match self.shape {
+      PolygonShape::Any => true,
+      PolygonShape::Simple => is_simple(points),
+      PolygonShape::Convex => is_convex(points),
+    }
+  }
+
+  /// Make `points` meet the constraints: drop the least significant
+  /// vertices down to the limit, then untangle crossing edges or take the
+  /// convex hull. Returns whether anything changed.
+  pub fn repair_points(&self, points: &mut Vec<(f32, f32)>) -> bool {
+    if self.allows(points) {
+      return false;
+    }
+    if let Some(max) = self.max_vertices {
+      remove_flattest(points, max.max(3));
+    }
+    match self.shape {
+      PolygonShape::Any => {}
+      PolygonShape::Simple => {
+        if !is_simple(points) {
+          untangle(points);
+        }
+      }
+      PolygonShape::Convex => {
+        if !is_convex(points) {
+          let hull = convex_hull(points);
+          if hull.len() >= 3 {
+            *points = hull;
+          }
+        }
+      }
+    }
+    true
+  }
+
+  /// Repair every invalid polygon of `image`, returning it and the number
+  /// of polygons changed
+  pub fn repair(&self, mut image: Image) -> (Image, usize) {
+    if !self.is_active() {
+      return (image, 0);
+    }
+    let mut repaired = 0;
+    for polygon in &mut image.polygon {
+      if self.repair_points(&mut polygon.points) {
+        repaired += 1;
+      }
+    }
+    (image, repaired)
+  }
+}
+
+fn cross(o: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+  (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+/// Whether segments ab and cd cross at a point interior to both
+fn segments_cross(a: (f32, f32), b: (f32, f32), c: (f32, f32), d: (f32, f32)) -> bool {
+  let (d1, d2) = (cross(a, b, c), cross(a, b, d));
+  let (d3, d4) = (cross(c, d, a), cross(c, d, b));
+  d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+/// First pair of non-adjacent edges that cross, as their start indices
+fn first_crossing(points: &[(f32, f32)]) -> Option<(usize, usize)> {
+  let n = points.len();
+  for i in 0..n {
+    // Edges i and i + 1 share a vertex, as do the last and first
+    for j in i + 2..n {
+      if i == 0 && j == n - 1 {
+        continue;
+      }
+      if segments_cross(points[i], points[(i + 1) % n], points[j], points[(j + 1) % n]) {
+        return Some((i, j));
+      }
+    }
+  }
+  None
+}
+
+/// No two edges cross
+pub fn is_simple(points: &[(f32, f32)]) -> bool {
+  points.len() <= 3 || first_crossing(points).is_none()
+}
+
+/// Simple, with every corner turning the same way
+pub fn is_convex(points: &[(f32, f32)]) -> bool {
+  let n = points.len();
+  let (mut left, mut right) = (false, false);
+  for i in 0..n {
+    let turn = cross(points[i], points[(i + 1) % n], points[(i + 2) % n]);
+    left |= turn > 0.0;
+    right |= turn < 0.0;
+  }
+  // A pentagram turns one way throughout but crosses itself
+  !(left && right) && is_simple(points)
+}
+
+/// Convex hull by Andrew's monotone chain
+pub fn convex_hull(points: &[(f32, f32)]) -> Vec<(f32, f32)> {
+  let mut sorted = points.to_vec();
+  sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+  sorted.dedup();
+  if sorted.len() < 3 {
+    return sorted;
+  }
+
+  let mut hull: Vec<(f32, f32)> = Vec::with_capacity(sorted.len() * 2);
+  for pass in 0..2 {
+    let start = hull.len();
+    for &point in sorted.iter() {
+      while hull.len() >= start + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0 {
+        hull.pop();
+      }
+      hull.push(point);
+    }
+    // Each chain's last point starts the other
+    hull.pop();
+    if pass == 0 {
+      sorted.reverse();
+    }
+  }
+  hull
+}
+
+/// Remove vertices spanning the smallest triangle with their neighbours
+/// until at most `max` remain
+fn remove_flattest(points: &mut Vec<(f32, f32)>, max: usize) {
+  while points.len() > max {
+    let n = points.len();
+    let flattest = (0..n)
+      .min_by(|&a, &b| {
+        let area = |i: usize| cross(points[(i + n - 1) % n], points[i], points[(i + 1) % n]).abs();
+        area(a).partial_cmp(&area(b)).unwrap_or(std::cmp::Ordering::Equal)
+      })
+      .unwrap();
+    points.remove(flattest);
+  }
+}
+
+/// Remove crossings with 2-opt moves, each of which shortens the outline;
+/// if that stalls, order the vertices by angle around their centroid
+fn untangle(points: &mut [(f32, f32)]) {
+  for _ in 0..MAX_UNTANGLE_PASSES * points.len() {
+    match first_crossing(points) {
+      Some((i, j)) => points[i + 1..=j].reverse(),
+      None => return,
+    }
+  }
+
+  let n = points.len() as f32;
+  let cx = points.iter().map(|p| p.0).sum::<f32>() / n;
+  let cy = points.iter().map(|p| p.1).sum::<f32>() / n;
+  let angle = |p: &(f32, f32)| (p.1 - cy).atan2(p.0 - cx);
+  points.sort_by(|a, b| angle(a).partial_cmp(&angle(b)).unwrap_or(std::cmp::Ordering::Equal));
+}
+
+/// Index to insert `point` at that lengthens the outline least
+pub fn insertion_index(points: &[(f32, f32)], point: (f32, f32)) -> usize {
+  let n = points.len();
+  if n < 2 {
+    return n;
+  }
+  let distance = |a: (f32, f32), b: (f32, f32)| ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+  (0..n)
+    .min_by(|&a, &b| {
+      let cost = |i: usize| {
+        let (p, q) = (points[i], points[(i + 1) % n]);
+        distance(p, point) + distance(point, q) - distance(p, q)
+      };
+      cost(a).partial_cmp(&cost(b)).unwrap_or(std::cmp::Ordering::Equal)
+    })
+    .map(|i| i + 1)
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn hull_drops_interior_and_collinear_points() {
+    let points = [(0.0, 0.0), (2.0, 0.0), (1.0, 1.0), (2.0, 2.0), (0.0, 2.0), (1.0, 0.0)];
+    let hull = convex_hull(&points);
+    assert_eq!(hull.len(), 4);
+    for corner in [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)] {
+      assert!(hull.contains(&corner));
+    }
+    assert!(is_convex(&hull));
+  }
+
+  #[test]
+  fn hull_of_too_few_points_is_unchanged() {
+    assert_eq!(convex_hull(&[(1.0, 1.0), (1.0, 1.0), (3.0, 2.0)]), vec![(1.0, 1.0), (3.0, 2.0)]);
+  }
+
+  #[test]
+  fn simple_polygons() {
+    let square = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+    let bowtie = [(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0)];
+    assert!(is_simple(&square));
+    assert!(!is_simple(&bowtie));
+    assert!(is_simple(&[(0.0, 0.0), (2.0, 2.0), (2.0, 0.0)]));
+  }
+}
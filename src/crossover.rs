@@ -0,0 +1,294 @@
+use std::borrow::Cow;
+
+use rand::prelude::*;
+
+use crate::random::rng;
+use crate::types::{Image, Polygon};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrossoverType {
+  /// Each polygon slot is taken from either parent with equal chance
+  Uniform,
+  /// Child is the bottom layers of one parent and the top layers of the other
+  OnePoint,
+  /// Child takes a middle band of layers from the second parent
+  TwoPoint,
+  /// Canvas is split by a random line and each side takes its shapes from one parent
+  Spatial,
+}
+
+impl CrossoverType {
+  pub fn from_name(name: &str) -> Option<CrossoverType> {
+    match name {
+      "uniform" => Some(CrossoverType::Uniform),
+      "one-point" => Some(CrossoverType::OnePoint),
+      "two-point" => Some(CrossoverType::TwoPoint),
+      "spatial" => Some(CrossoverType::Spatial),
+      _ => None,
+    }
+  }
+}
+
+/// `b` on `a`'s canvas, so polygons from both parents line up in the child
+fn on_canvas_of<'b>(a: &Image, b: &'b Image) -> Cow<'b, Image> {
+  if (a.width, a.height) == (b.width, b.height) {
+    Cow::Borrowed(b)
+  } else {
+    Cow::Owned(b.rescaled(a.width, a.height))
+  }
+}
+
+fn child_of(a: &Image, b: &Image, polygon: Vec<Polygon>) -> Image {
+  Image {
+    polygon,
+    width: a.width,
+    height: a.height,
+    step_size: (a.step_size * b.step_size).sqrt(),
+  }
+}
+
+/// Cut index at the same relative depth in a polygon list of length `len`
+fn cut_at(fraction: f32, len: usize) -> usize {
+  ((fraction * len as f32).round() as usize).min(len)
+}
+
+/// Polygon-wise uniform crossover. Where only the longer parent has a polygon
+/// it is inherited with probability 0.5, so the child's length lies between
+/// the parents' lengths.
+pub fn uniform_crossover(a: &Image, b: &Image) -> Image {
+  binomial_crossover(a, b, 0.5)
+}
+
+/// Polygon-wise crossover taking each slot from `a` with probability `rate`
+/// and from `b` otherwise. A polygon only one parent has is kept with the
+/// chance of taking that parent's slot, so neither parent's extra polygons
+/// are dropped outright.
+pub fn binomial_crossover(a: &Image, b: &Image, rate: f32) -> Image {
+  let b = &on_canvas_of(a, b);
+  let mut rng = rng();
+  let rate = rate.clamp(0.0, 1.0) as f64;
+  let len = a.polygon.len().max(b.polygon.len());
+
+  let polygon = (0..len)
+    .filter_map(|i| match (a.polygon.get(i), b.polygon.get(i)) {
+      (Some(pa), Some(pb)) => Some(if rng.random_bool(rate) { pa } else { pb }),
+      (Some(p), None) => rng.random_bool(rate).then_some(p),
+      (None, Some(p)) => rng.random_bool(1.0 - rate).then_some(p),
+      (None, None) => None,
+    })
+    .cloned()
+    .collect();
+
+  child_of(a, b, polygon)
+}
+
+/// One-point crossover on the polygon list. The cut is placed at the same
+/// relative depth in both parents so layers keep their rough z-order.
+pub fn one_point_crossover(a: &Image, b: &Image) -> Image {
+  let b = &on_canvas_of(a, b);
+  let fraction: f32 = rng().random();
+  let cut_a = cut_at(fraction, a.polygon.len());
+  let cut_b = cut_at(fraction, b.polygon.len());
+
+  let polygon = a.polygon[..cut_a]
+    .iter()
+    .chain(b.polygon[cut_b..].iter())
+    .cloned()
+    .collect();
+
+  child_of(a, b, polygon)
+}
+
+/// Two-point crossover: `a` below and above, a band of `b` in between
+pub fn two_point_crossover(a: &Image, b: &Image) -> Image {
+  let b = &on_canvas_of(a, b);
+  let mut rng = rng();
+  let (mut f1, mut f2): (f32, f32) = (rng.random(), rng.random());
+  if f1 > f2 {
+    std::mem::swap(&mut f1, &mut f2);
+  }
+
+  let (a1, a2) = (cut_at(f1, a.polygon.len()), cut_at(f2, a.polygon.len()));
+  let (b1, b2) = (cut_at(f1, b.polygon.len()), cut_at(f2, b.polygon.len()));
+
+  let polygon = a.polygon[..a1]
+    .iter()
+    .chain(b.polygon[b1..b2].iter())
+    .chain(a.polygon[a2..].iter())
+    .cloned()
+    .collect();
+
+  child_of(a, b, polygon)
+}
+
+fn centroid(polygon: &Polygon) -> (f32, f32) {
+  if polygon.points.is_empty() {
+    return (0.0, 0.0);
+  }
+  let n = polygon.points.len() as f32;
+  let (sx, sy) = polygon
+    .points
+    .iter()
+    .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+  (sx / n, sy / n)
+}
+
+/// Spatial crossover: a random line through the canvas splits it in two.
+/// Shapes whose centroid falls on one side come from `a`, the rest from `b`.
+/// The two selections are merged by relative depth to preserve layering.
+/// The child can have anywhere from none to all of both parents' shapes.
+pub fn spatial_crossover(a: &Image, b: &Image) -> Image {
+  let b = &on_canvas_of(a, b);
+  let mut rng = rng();
+  let origin = (
+    rng.random_range(0.0..a.width.max(1) as f32),
+    rng.random_range(0.0..a.height.max(1) as f32),
+  );
+  let angle: f32 = rng.random_range(0.0..std::f32::consts::TAU);
+  let normal = (angle.cos(), angle.sin());
+
+  let side = |polygon: &Polygon| {
+    let (cx, cy) = centroid(polygon);
+    (cx - origin.0) * normal.0 + (cy - origin.1) * normal.1 >= 0.0
+  };
+
+  let depth = |i: usize, len: usize| (i as f32 + 0.5) / len as f32;
+
+  let mut layers: Vec<(f32, &Polygon)> = a
+    .polygon
+    .iter()
+    .enumerate()
+    .filter(|(_, p)| side(p))
+    .map(|(i, p)| (depth(i, a.polygon.len()), p))
+    .chain(
+      b.polygon
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| !side(p))
+        .map(|(i, p)| (depth(i, b.polygon.len()), p)),
+    )
+    .collect();
+  layers.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+
+  let polygon = layers.into_iter().map(|(_, p)| p.clone()).collect();
+  child_of(a, b, polygon)
+}
+
+/// Recombine `a` and `b`, with the child on `a`'s canvas. A child with
+/// fewer than `min_polygons` polygons, which mutations would never produce,
+/// is replaced by the parent with more of them.
+pub fn crossover(a: &Image, b: &Image, crossover_type: CrossoverType, min_polygons: usize) -> Image {
+  let child = match crossover_type {
+    CrossoverType::Uniform => uniform_crossover(a, b),
+    CrossoverType::OnePoint => one_point_crossover(a, b),
+    CrossoverType::TwoPoint => two_point_crossover(a, b),
+    CrossoverType::Spatial => spatial_crossover(a, b),
+  };
+  if child.polygon.len() >= min_polygons {
+    return child;
+  }
+
+  let b = on_canvas_of(a, b);
+  let fuller = if b.polygon.len() > a.polygon.len() {
+    &b.polygon
+  } else {
+    &a.polygon
+  };
+  child_of(a, &b, fuller.clone())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::random;
+
+  /// A parent of `count` triangles on a 100 x 100 canvas, each tagged by
+  /// its colour with the parent and its index. Parent 1 spreads them over
+  /// the canvas and parent 2 packs them into a corner, so a spatial split
+  /// rarely takes as many shapes from one as from the other.
+  fn parent(tag: u8, count: usize) -> Image {
+    let (origin, spacing) = if tag == 1 { (0.0, 25.0) } else { (70.0, 5.0) };
+    Image {
+      polygon: (0..count)
+        .map(|i| {
+          let (x, y) = (origin + (i % 4) as f32 * spacing, origin + (i / 4) as f32 * spacing);
+          Polygon {
+            points: vec![(x, y), (x + 20.0, y), (x, y + 20.0)],
+            colour: [tag, i as u8, 0, 255],
+          }
+        })
+        .collect(),
+      width: 100,
+      height: 100,
+      step_size: 1.0,
+    }
+  }
+
+  fn from(parent: &Image, polygon: &Polygon) -> bool {
+    parent.polygon.contains(polygon)
+  }
+
+  #[test]
+  fn uniform_takes_each_slot_from_either_parent() {
+    random::seed(1);
+    let (a, b) = (parent(1, 12), parent(2, 12));
+    for _ in 0..50 {
+      let child = crossover(&a, &b, CrossoverType::Uniform, 10);
+      assert_eq!(child.polygon.len(), 12);
+      for (i, polygon) in child.polygon.iter().enumerate() {
+        assert!(*polygon == a.polygon[i] || *polygon == b.polygon[i]);
+      }
+    }
+  }
+
+  #[test]
+  fn one_point_is_a_prefix_of_one_parent_and_suffix_of_the_other() {
+    random::seed(2);
+    let (a, b) = (parent(1, 12), parent(2, 12));
+    for _ in 0..50 {
+      let child = crossover(&a, &b, CrossoverType::OnePoint, 10);
+      assert_eq!(child.polygon.len(), 12);
+      let cut = child.polygon.iter().take_while(|p| from(&a, p)).count();
+      assert!(child.polygon[..cut] == a.polygon[..cut]);
+      assert!(child.polygon[cut..] == b.polygon[cut..]);
+    }
+  }
+
+  #[test]
+  fn two_point_puts_a_band_of_one_parent_inside_the_other() {
+    random::seed(3);
+    let (a, b) = (parent(1, 12), parent(2, 12));
+    for _ in 0..50 {
+      let child = crossover(&a, &b, CrossoverType::TwoPoint, 10);
+      assert_eq!(child.polygon.len(), 12);
+      let start = child.polygon.iter().take_while(|p| from(&a, p)).count();
+      let end = start + child.polygon[start..].iter().take_while(|p| from(&b, p)).count();
+      assert!(child.polygon[..start] == a.polygon[..start]);
+      assert!(child.polygon[start..end] == b.polygon[start..end]);
+      assert!(child.polygon[end..] == a.polygon[end..]);
+    }
+  }
+
+  #[test]
+  fn spatial_keeps_parent_shapes_and_the_polygon_minimum() {
+    random::seed(4);
+    let (a, b) = (parent(1, 12), parent(2, 12));
+    for _ in 0..200 {
+      let child = crossover(&a, &b, CrossoverType::Spatial, 10);
+      assert!((10..=24).contains(&child.polygon.len()));
+      assert!(child.polygon.iter().all(|p| from(&a, p) || from(&b, p)));
+    }
+  }
+
+  #[test]
+  fn rescales_a_parent_on_another_canvas() {
+    random::seed(5);
+    let (a, b) = (parent(1, 12), parent(2, 12));
+    let stretched = b.rescaled(200, 50);
+    for _ in 0..20 {
+      let child = crossover(&a, &stretched, CrossoverType::Uniform, 0);
+      assert_eq!((child.width, child.height), (100, 100));
+      assert!(child.polygon.iter().all(|p| from(&a, p) || from(&b, p)));
+    }
+  }
+}
@@ -0,0 +1,105 @@
+use crate::generations::{generate_random_colour, generate_random_point};
+use crate::types::{Image, Polygon};
+
+/// Fixed polygon and vertex layout for flattening an `Image` into a real vector.
+///
+/// Each polygon contributes its four colour channels followed by its vertex
+/// coordinates. All values are normalised to [0, 1] (channels by 255,
+/// coordinates by the canvas size) so every dimension has the same scale.
+#[derive(Clone, Debug)]
+pub struct Topology {
+  pub vertex_counts: Vec<usize>,
+  pub width: usize,
+  pub height: usize,
+}
+
+impl Topology {
+  pub fn of(image: &Image) -> Self {
+    Self {
+      vertex_counts: image.polygon.iter().map(|p| p.points.len()).collect(),
+      width: image.width,
+      height: image.height,
+    }
+  }
+
+  pub fn dimension(&self) -> usize {
+    self.vertex_counts.iter().map(|n| 4 + 2 * n).sum()
+  }
+
+  /// Whether `image` has exactly this polygon and vertex layout
+  pub fn matches(&self, image: &Image) -> bool {
+    image.polygon.len() == self.vertex_counts.len()
+      && image
+        .polygon
+        .iter()
+        .zip(&self.vertex_counts)
+        .all(|(p, n)| p.points.len() == *n)
+  }
+
+  fn x_scale(&self) -> f32 {
+    (self.width as f32 - 1.0).max(1.0)
+  }
+
+  fn y_scale(&self) -> f32 {
+    (self.height as f32 - 1.0).max(1.0)
+  }
+
+  /// A fresh random image with this topology, drawn like `generate_initial_image`
+  pub fn random_image(&self) -> Image {
+    let polygon = self
+      .vertex_counts
+      .iter()
+      .map(|n| Polygon {
+        points: (0..*n)
+          .map(|_| generate_random_point(self.width, self.height))
+          .collect(),
+        colour: generate_random_colour(),
+      })
+      .collect();
+
+    Image {
+      polygon,
+      width: self.width,
+      height: self.height,
+      step_size: 1.0,
+    }
+  }
+
+  /// Flatten an image with this topology into normalised coordinates
+  pub fn encode(&self, image: &Image) -> Vec<f32> {
+    let mut genome = Vec::with_capacity(self.dimension());
+    for polygon in &image.polygon {
+      genome.extend(polygon.colour.iter().map(|c| *c as f32 / 255.0));
+      for (x, y) in &polygon.points {
+        genome.push(x / self.x_scale());
+        genome.push(y / self.y_scale());
+      }
+    }
+    genome
+  }
+
+  /// Rebuild an image from a vector, clamping values back into range
+  pub fn decode(&self, genome: &[f32]) -> Image {
+    let mut values = genome.iter().map(|v| v.clamp(0.0, 1.0));
+    let mut next = || values.next().unwrap_or(0.0);
+
+    let polygon = self
+      .vertex_counts
+      .iter()
+      .map(|n| {
+        let colour = [0; 4].map(|_: u8| (next() * 255.0).round() as u8);
+        let points = (0..*n)
+          .map(|_| (next() * self.x_scale(), next() * self.y_scale()))
+          .collect();
+        Polygon { points, colour }
+      })
+      .collect();
+
+    Image {
+      polygon,
+      width: self.width,
+      height: self.height,
+      step_size: 1.0,
+    }
+  }
+}
@@ -1,5 +1,6 @@
-use rand::{prelude::*, rng};
+use rand::prelude::*;
 
+use crate::random::rng;
 use crate::types::{Image, Polygon};
 
 const MAX_POINTS: usize = 6;
@@ -43,5 +44,6 @@ pub fn generate_initial_image(width: usize, height: usize) -> Image {
       .collect(),
     width,
     height,
+    step_size: 1.0,
   }
 }
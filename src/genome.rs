@@ -0,0 +1,120 @@
+use crate::types::{Image, Polygon};
+
+const HEADER: &str = "approx-image-gen genome v1";
+
+/// Plain-text form of a genome: a header line, `size WIDTH HEIGHT`, then one
+/// line per polygon from the bottom up, `polygon R G B A X1 Y1 X2 Y2 ...`
+pub fn to_text(image: &Image) -> String {
+  let mut text = format!("{}\nsize {} {}\n", HEADER, image.width, image.height);
+  for polygon in &image.polygon {
+    let [r, g, b, a] = polygon.colour;
+    text.push_str(&format!("polygon {} {} {} {}", r, g, b, a));
+    for (x, y) in &polygon.points {
+      text.push_str(&format!(" {} {}", x, y));
+    }
+    text.push('\n');
+  }
+  text
+}
+
+/// Parse the output of `to_text`. Blank lines and lines starting with `#`
+/// are ignored.
+pub fn from_text(text: &str) -> Result<Image, String> {
+  let mut lines = text
+    .lines()
+    .enumerate()
+    .map(|(i, line)| (i + 1, line.trim()))
+    .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+  match lines.next() {
+    Some((_, HEADER)) => {}
+    _ => return Err(format!("Missing genome header \"{}\"", HEADER)),
+  }
+
+  let (width, height) = match lines.next() {
+    Some((number, line)) => {
+      let fields: Vec<&str> = line.split_whitespace().collect();
+      match fields.as_slice() {
+        ["size", width, height] => (
+          width.parse().map_err(|_| format!("Line {}: invalid width", number))?,
+          height.parse().map_err(|_| format!("Line {}: invalid height", number))?,
+        ),
+        _ => return Err(format!("Line {}: expected size WIDTH HEIGHT", number)),
+      }
+    }
+    None => return Err("Missing size line".to_string()),
+  };
+
+  let mut polygon = Vec::new();
+  for (number, line) in lines {
+    let mut fields = line.split_whitespace();
+    if fields.next() != Some("polygon") {
+      return Err(format!("Line {}: expected polygon", number));
+    }
+    let values: Vec<&str> = fields.collect();
+    if values.len() < 4 || !(values.len() - 4).is_multiple_of(2) {
+      return Err(format!("Line {}: expected R G B A followed by X Y pairs", number));
+    }
+
+    let mut colour = [0u8; 4];
+    for (channel, value) in colour.iter_mut().zip(&values[..4]) {
+      *channel = value
+        .parse()
+        .map_err(|_| format!("Line {}: invalid colour {}", number, value))?;
+    }
+    let coordinates = values[4..]
+      .iter()
+      .map(|value| {
+        value
+          .parse::<f32>()
+          .map_err(|_| format!("Line {}: invalid coordinate {}", number, value))
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    let points = coordinates.chunks(2).map(|xy| (xy[0], xy[1])).collect();
+
+    polygon.push(Polygon { points, colour });
+  }
+
+  Ok(Image {
+    polygon,
+    width,
+    height,
+    step_size: 1.0,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn text_round_trip() {
+    let image = Image {
+      polygon: vec![
+        Polygon {
+          points: vec![(0.0, 0.0), (10.5, 2.25), (3.0, 7.0)],
+          colour: [255, 0, 128, 64],
+        },
+        Polygon {
+          points: vec![(1.0, 1.0), (2.0, 1.0), (2.0, 2.0), (1.0, 2.0)],
+          colour: [1, 2, 3, 4],
+        },
+      ],
+      width: 20,
+      height: 10,
+      step_size: 1.0,
+    };
+    let parsed = from_text(&to_text(&image)).unwrap();
+    assert_eq!((parsed.width, parsed.height), (20, 10));
+    assert!(parsed.polygon == image.polygon);
+  }
+
+  #[test]
+  fn skips_comments_and_reports_bad_lines() {
+    let text = format!("{}\n# comment\n\nsize 4 4\npolygon 1 2 3 4 0 0 1 0 1 1\n", HEADER);
+    assert_eq!(from_text(&text).unwrap().polygon.len(), 1);
+    assert!(from_text("size 4 4\n").is_err());
+    let odd = format!("{}\nsize 4 4\npolygon 1 2 3 4 0 0 1\n", HEADER);
+    assert_eq!(from_text(&odd).err().unwrap(), "Line 3: expected R G B A followed by X Y pairs");
+  }
+}
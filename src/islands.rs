@@ -0,0 +1,228 @@
+use std::thread;
+use std::time::Instant;
+
+use crate::algorithms_wasm::{AlgorithmConfig, AlgorithmState, AlgorithmType};
+use crate::observer::{Control, Observer, RunStatus};
+use crate::random::{self, derive_seed};
+use crate::types::Image;
+
+/// Which islands receive each island's best genome at migration time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MigrationTopology {
+  /// Island i sends to island i + 1, wrapping around
+  Ring,
+  /// Every island receives the best genome of all the others
+  FullyConnected,
+}
+
+impl MigrationTopology {
+  pub fn from_name(name: &str) -> Option<MigrationTopology> {
+    match name {
+      "ring" => Some(MigrationTopology::Ring),
+      "full" => Some(MigrationTopology::FullyConnected),
+      _ => None,
+    }
+  }
+}
+
+pub struct IslandConfig {
+  /// One optimiser per island; algorithms and settings may differ
+  pub islands: Vec<AlgorithmConfig>,
+  /// Iterations each island runs between migrations
+  pub migration_interval: usize,
+  pub topology: MigrationTopology,
+  /// Seeds each island's thread every migration round, as the threads don't
+  /// outlive a round, for a repeatable run; None draws from entropy
+  pub seed: Option<u64>,
+}
+
+/// Seed this thread for `island`'s work in migration round `round`
+fn seed_island(seed: Option<u64>, island: usize, round: u64) {
+  if let Some(seed) = seed {
+    random::seed(derive_seed(derive_seed(seed, island as u64), round));
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct IslandReport {
+  pub island: usize,
+  pub algorithm: AlgorithmType,
+  pub iteration: usize,
+  pub best_score: f32,
+}
+
+pub struct IslandResult {
+  pub islands: Vec<IslandReport>,
+  pub best_score: f32,
+  pub best: Image,
+}
+
+fn reports(states: &[AlgorithmState]) -> Vec<IslandReport> {
+  states
+    .iter()
+    .enumerate()
+    .map(|(island, state)| IslandReport {
+      island,
+      algorithm: state.algorithm_type(),
+      iteration: state.get_iteration(),
+      best_score: state.get_accuracy(),
+    })
+    .collect()
+}
+
+fn best_island(states: &[AlgorithmState]) -> &AlgorithmState {
+  states
+    .iter()
+    .max_by(|a, b| a.get_accuracy().partial_cmp(&b.get_accuracy()).unwrap())
+    .expect("island model needs at least one island")
+}
+
+/// The whole model's progress as seen through its best island: iterations
+/// of the furthest island and evaluations summed over all of them
+fn status(states: &[AlgorithmState], start: Instant) -> RunStatus<'_> {
+  RunStatus {
+    iteration: states.iter().map(|s| s.get_iteration()).max().unwrap_or(0),
+    evaluations: states.iter().map(|s| s.evaluations()).sum(),
+    elapsed_seconds: start.elapsed().as_secs_f64(),
+    ..best_island(states).status(None)
+  }
+}
+
+fn migrate(states: &mut [AlgorithmState], topology: MigrationTopology) {
+  let count = states.len();
+  if count < 2 {
+    return;
+  }
+
+  let bests: Vec<(f32, Image)> = states
+    .iter()
+    .map(|s| (s.get_accuracy(), s.best_image().clone()))
+    .collect();
+
+  for (i, state) in states.iter_mut().enumerate() {
+    let migrant = match topology {
+      MigrationTopology::Ring => &bests[(i + count - 1) % count].1,
+      MigrationTopology::FullyConnected => {
+        let (_, best) = bests
+          .iter()
+          .enumerate()
+          .filter(|(j, _)| *j != i)
+          .map(|(_, b)| b)
+          .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+          .unwrap();
+        best
+      }
+    };
+    state.inject(migrant.clone());
+  }
+}
+
+/// Run one optimiser per island on its own thread, exchanging best genomes
+/// every `migration_interval` iterations until every island has finished.
+/// After each migration round `on_epoch` gets per-island progress and
+/// `observer` the model's, which may cancel the run.
+pub fn run_islands<F: FnMut(&[IslandReport])>(
+  target_pixels: &[u8],
+  width: usize,
+  height: usize,
+  config: IslandConfig,
+  observer: &mut dyn Observer,
+  mut on_epoch: F,
+) -> IslandResult {
+  let start = Instant::now();
+  let interval = config.migration_interval.max(1);
+  let seed = config.seed;
+
+  let mut states: Vec<AlgorithmState> = thread::scope(|scope| {
+    let handles: Vec<_> = config
+      .islands
+      .into_iter()
+      .enumerate()
+      .map(|(island, algorithm)| {
+        scope.spawn(move || {
+          seed_island(seed, island, 0);
+          AlgorithmState::new(target_pixels.to_vec(), width, height, algorithm)
+        })
+      })
+      .collect();
+    handles.into_iter().map(|h| h.join().unwrap()).collect()
+  });
+
+  let mut round = 0;
+  while states.iter().any(|s| !s.is_finished()) {
+    round += 1;
+    let before: usize = states.iter().map(|s| s.get_iteration()).sum();
+    thread::scope(|scope| {
+      for (island, state) in states.iter_mut().enumerate() {
+        scope.spawn(move || {
+          seed_island(seed, island, round);
+          state.run_iterations(interval);
+        });
+      }
+    });
+    // Islands only find they are finished when they try another iteration
+    if states.iter().map(|s| s.get_iteration()).sum::<usize>() == before {
+      break;
+    }
+
+    migrate(&mut states, config.topology);
+    on_epoch(&reports(&states));
+    if observer.on_iteration(&status(&states, start)) == Control::Cancel {
+      break;
+    }
+  }
+  observer.on_finish(&status(&states, start));
+
+  let islands = reports(&states);
+  let winner = best_island(&states);
+
+  IslandResult {
+    islands,
+    best_score: winner.get_accuracy(),
+    best: winner.best_image().clone(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn config(iterations: usize) -> IslandConfig {
+    let algorithm = AlgorithmConfig {
+      max_iterations: iterations,
+      target_accuracy: 2.0,
+      ..AlgorithmConfig::default()
+    };
+    IslandConfig {
+      islands: vec![algorithm; 2],
+      migration_interval: 10,
+      topology: MigrationTopology::Ring,
+      seed: Some(3),
+    }
+  }
+
+  #[test]
+  fn observer_sees_every_migration_round() {
+    let target = vec![90; 12 * 10 * 4];
+    let mut seen = Vec::new();
+    let mut observer = |status: &RunStatus| {
+      seen.push((status.iteration, status.evaluations, status.best_score));
+      Control::Continue
+    };
+    let result = run_islands(&target, 12, 10, config(30), &mut observer, |_| {});
+
+    let iterations: Vec<usize> = seen.iter().map(|s| s.0).collect();
+    assert_eq!(iterations, vec![10, 20, 30]);
+    assert!(seen.windows(2).all(|w| w[1].1 > w[0].1));
+    assert_eq!(seen.last().unwrap().2, result.best_score);
+  }
+
+  #[test]
+  fn cancelling_stops_every_island() {
+    let target = vec![90; 12 * 10 * 4];
+    let mut observer = |_: &RunStatus| Control::Cancel;
+    let result = run_islands(&target, 12, 10, config(1000), &mut observer, |_| {});
+
+    assert!(result.islands.iter().all(|island| island.iteration == 10));
+  }
+}
@@ -0,0 +1,180 @@
+use crate::renderer_wasm::{draw_polygons, PixelBuffer};
+use crate::types::{Image, Polygon};
+
+/// Fewest layers painted between saved canvases
+pub const CHECKPOINT_INTERVAL: usize = 8;
+// Reference genomes kept; the best scoring, which are usually the parents
+const REFERENCES: usize = 4;
+// Bytes all checkpoints may take together. Each is a full canvas, so large
+// canvases get sparser checkpoints, then fewer references.
+const MAX_CHECKPOINT_BYTES: usize = 64 << 20;
+// Renders in a row with no usable checkpoint before the references are
+// dropped, e.g. after a restart replaced the population
+const MAX_MISSES: usize = 64;
+
+/// A genome and the canvas after every `interval` of its layers
+struct Reference {
+  polygons: Vec<Polygon>,
+  score: f32,
+  interval: usize,
+  checkpoints: Vec<PixelBuffer>,
+}
+
+/// Renders genomes incrementally. Mutating polygon k of N leaves the
+/// canvas beneath it unchanged, so a genome sharing its first layers with
+/// a cached reference is painted from the deepest checkpoint the two have
+/// in common instead of from a blank canvas.
+pub struct LayerCache {
+  references: Vec<Reference>,
+  scratch: PixelBuffer,
+  misses: usize,
+}
+
+impl Default for LayerCache {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Number of leading polygons `a` and `b` share
+fn common_prefix(a: &[Polygon], b: &[Polygon]) -> usize {
+  a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// References kept, and checkpoints each may save, within
+/// `MAX_CHECKPOINT_BYTES` for a `width` x `height` canvas
+fn budget(width: usize, height: usize) -> (usize, usize) {
+  let canvases = MAX_CHECKPOINT_BYTES / (width * height * 4).max(1);
+  let references = canvases.min(REFERENCES);
+  (references, canvases.checked_div(references).unwrap_or(0))
+}
+
+impl LayerCache {
+  /// An empty cache; buffers are sized by the first genome rendered
+  pub fn new() -> Self {
+    Self {
+      references: Vec::new(),
+      scratch: PixelBuffer::new(0, 0),
+      misses: 0,
+    }
+  }
+
+  /// Render `image`, pixel for pixel as `render_image` would, into a
+  /// buffer reused between calls
+  pub fn render(&mut self, image: &Image) -> &PixelBuffer {
+    if (self.scratch.width, self.scratch.height) != (image.width, image.height) {
+      self.scratch = PixelBuffer::new(image.width, image.height);
+      self.references.clear();
+    }
+
+    // Deepest checkpoint below the first layer that differs
+    let deepest = self
+      .references
+      .iter()
+      .map(|reference| {
+        let depth = common_prefix(&reference.polygons, &image.polygon) / reference.interval;
+        (depth, reference)
+      })
+      .max_by_key(|(depth, reference)| depth * reference.interval)
+      .filter(|(depth, _)| *depth > 0);
+
+    match deepest {
+      Some((depth, reference)) => {
+        self.misses = 0;
+        self.scratch.data.copy_from_slice(&reference.checkpoints[depth - 1].data);
+        draw_polygons(&mut self.scratch, &image.polygon[depth * reference.interval..]);
+      }
+      None => {
+        self.misses += 1;
+        self.scratch.clear();
+        draw_polygons(&mut self.scratch, &image.polygon);
+      }
+    }
+    &self.scratch
+  }
+
+  /// Keep `image`, which scored `score`, as a reference if there is room
+  /// or it beats the worst one. Each reference costs a canvas per
+  /// checkpoint, up to `MAX_CHECKPOINT_BYTES` for all of them together.
+  pub fn offer(&mut self, image: &Image, score: f32) {
+    if self.misses > MAX_MISSES {
+      self.references.clear();
+      self.misses = 0;
+    }
+    let (references, checkpoints) = budget(image.width, image.height);
+    if references == 0
+      || image.polygon.len() < CHECKPOINT_INTERVAL
+      || (self.scratch.width, self.scratch.height) != (image.width, image.height)
+      || self.references.iter().any(|reference| reference.polygons == image.polygon)
+    {
+      return;
+    }
+
+    let worst = self
+      .references
+      .iter()
+      .enumerate()
+      .min_by(|a, b| a.1.score.total_cmp(&b.1.score))
+      .map(|(index, reference)| (index, reference.score));
+    let slot = match worst {
+      _ if self.references.len() < references => None,
+      Some((index, worst_score)) if score > worst_score => Some(index),
+      _ => return,
+    };
+
+    let interval = image.polygon.len().div_ceil(checkpoints).max(CHECKPOINT_INTERVAL);
+    let mut canvas = PixelBuffer::new(image.width, image.height);
+    let mut saved = Vec::new();
+    for layers in image.polygon.chunks_exact(interval) {
+      draw_polygons(&mut canvas, layers);
+      saved.push(canvas.clone());
+    }
+    let reference = Reference {
+      polygons: image.polygon.clone(),
+      score,
+      interval,
+      checkpoints: saved,
+    };
+    match slot {
+      Some(index) => self.references[index] = reference,
+      None => self.references.push(reference),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mutations::{mutate_image, MutationConfig};
+  use crate::random;
+  use crate::renderer_wasm::render_image;
+
+  #[test]
+  fn renders_as_render_image() {
+    random::seed(7);
+    let config = MutationConfig::default();
+    let mut cache = LayerCache::new();
+    let mut parent = config.initial_image(40, 30);
+    for _ in 0..40 {
+      parent = mutate_image(parent, &config, None).0;
+    }
+
+    // Children of a drifting parent, so checkpoints are hit and replaced
+    for i in 0..300 {
+      let child = mutate_image(parent.clone(), &config, None).0;
+      assert_eq!(cache.render(&child).data, render_image(&child).data);
+      cache.offer(&child, i as f32);
+      if i % 10 == 0 {
+        parent = child;
+      }
+    }
+  }
+
+  #[test]
+  fn checkpoints_fit_the_budget() {
+    let (references, checkpoints) = budget(1000, 1000);
+    assert!(references * checkpoints * 1000 * 1000 * 4 <= MAX_CHECKPOINT_BYTES);
+    assert_eq!(budget(40, 30).0, REFERENCES);
+    assert_eq!(budget(8000, 8000), (0, 0));
+  }
+}
@@ -1,21 +1,53 @@
 // Shared modules (always available)
+pub mod cmaes;
+pub mod colour_solve;
+pub mod constraints;
+pub mod crossover;
+pub mod encoding;
 pub mod generations;
+pub mod genome;
+pub mod layer_cache;
 pub mod mutations;
+pub mod nsga2;
+pub mod observer;
+pub mod palette;
+pub mod pso;
+pub mod random;
+pub mod restarts;
 pub mod scoring;
+pub mod selection;
+pub mod simplify;
+pub mod stopping;
 pub mod types;
 
+// Pure-Rust algorithms and renderer; driven by the WASM bindings and the CLI
+pub mod algorithms_wasm;
+pub mod renderer_wasm;
+
 // CLI-only modules (use imageproc)
 #[cfg(feature = "cli")]
 pub mod algorithms;
 #[cfg(feature = "cli")]
 pub mod renderer;
 
+// Native-only modules (use OS threads, clocks and files)
+#[cfg(feature = "cli")]
+pub mod batch;
+#[cfg(feature = "cli")]
+pub mod benchmark;
+#[cfg(feature = "cli")]
+pub mod islands;
+#[cfg(feature = "cli")]
+pub mod metrics;
+#[cfg(feature = "cli")]
+pub mod recorder;
+#[cfg(feature = "cli")]
+pub mod sequence;
+#[cfg(feature = "cli")]
+pub mod tiles;
+
 // WASM-only modules
 #[cfg(feature = "wasm")]
-pub mod algorithms_wasm;
-#[cfg(feature = "wasm")]
-pub mod renderer_wasm;
-#[cfg(feature = "wasm")]
 pub mod wasm_bindings;
 
 // Re-export WASM bindings for wasm-pack
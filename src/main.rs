@@ -1,38 +1,1485 @@
 #[cfg(feature = "cli")]
-mod algorithms;
+use image::imageops::FilterType;
 #[cfg(feature = "cli")]
-mod renderer;
+use image::{ImageReader, RgbaImage};
 
-mod generations;
-mod mutations;
-mod scoring;
-mod types;
+#[cfg(feature = "cli")]
+use approx_image_gen::algorithms::strongest_mutates_alg;
+#[cfg(feature = "cli")]
+use approx_image_gen::algorithms_wasm::{AlgorithmConfig, AlgorithmState, AlgorithmType, Replacement};
+#[cfg(feature = "cli")]
+use approx_image_gen::batch::{find_jobs, run_batch, summary_csv, summary_line, BatchJob, JobOutcome, JobStatus};
+#[cfg(feature = "cli")]
+use approx_image_gen::benchmark::{
+    budget_note, format_table, run_benchmark, summaries_to_csv, summarise, to_json, BenchmarkAlgorithm, BenchmarkCase,
+    BenchmarkConfig,
+    BenchmarkTarget,
+};
+#[cfg(feature = "cli")]
+use approx_image_gen::cmaes::{refine_with_cmaes, Covariance};
+#[cfg(feature = "cli")]
+use approx_image_gen::colour_solve::solve_all_colours;
+#[cfg(feature = "cli")]
+use approx_image_gen::constraints::PolygonShape;
+#[cfg(feature = "cli")]
+use approx_image_gen::crossover::CrossoverType;
+#[cfg(feature = "cli")]
+use approx_image_gen::genome;
+#[cfg(feature = "cli")]
+use approx_image_gen::islands::{run_islands, IslandConfig, MigrationTopology};
+#[cfg(feature = "cli")]
+use approx_image_gen::metrics::{MetricsFormat, MetricsLog};
+#[cfg(feature = "cli")]
+use approx_image_gen::mutations::{check_delta, MutationType, Perturbation, StepAdaptation, StepScale};
+#[cfg(feature = "cli")]
+use approx_image_gen::nsga2::Complexity;
+#[cfg(feature = "cli")]
+use approx_image_gen::observer::{Control, ConsoleProgress, Observer, RunStatus, SnapshotSaver};
+#[cfg(feature = "cli")]
+use approx_image_gen::palette::{Palette, Quantizer};
+#[cfg(feature = "cli")]
+use approx_image_gen::recorder::{write_animation, AnimationFormat, Capture, Recorder, RecorderConfig};
+#[cfg(feature = "cli")]
+use approx_image_gen::random::{self, derive_seed};
+#[cfg(feature = "cli")]
+use approx_image_gen::renderer::{render_image, render_image_at};
+#[cfg(feature = "cli")]
+use approx_image_gen::renderer_wasm;
+#[cfg(feature = "cli")]
+use approx_image_gen::restarts::RestartStrategy;
+#[cfg(feature = "cli")]
+use approx_image_gen::scoring::{mse_compare, sad_compare, Evaluator, Metric};
+#[cfg(feature = "cli")]
+use approx_image_gen::selection::Selection;
+#[cfg(feature = "cli")]
+use approx_image_gen::sequence::{load_frames, run_sequence, SequenceConfig};
+#[cfg(feature = "cli")]
+use approx_image_gen::simplify::{simplify, SimplifyConfig};
+#[cfg(feature = "cli")]
+use approx_image_gen::stopping::StopCondition;
+#[cfg(feature = "cli")]
+use approx_image_gen::tiles::{run_tiled, TileConfig};
+#[cfg(feature = "cli")]
+use approx_image_gen::types::Image;
+
+#[cfg(feature = "cli")]
+const PROGRESS_INTERVAL: usize = 500;
+
+#[cfg(feature = "cli")]
+const USAGE: &str = "Usage: approx-image-gen [options]
+       approx-image-gen benchmark [benchmark options] [options]
+       approx-image-gen sequence --input <frames> [--temporal-weight <x>] [options]
+       approx-image-gen batch --input <dir or glob> [--workers <n>] [options]
+       approx-image-gen render --input <genome> [--output <path>] [--output-size <w>x<h>]
+                               [--polygon-shape <kind>] [--max-vertices <n>] [--palette <path>]
+
+Options:
+  --input <path>                 Target image (default: ./resources/rust.png)
+  --output <path>                Output image (default: ./resources/output.png)
+  --output-size <w>x<h>          Render the result at this size (default: the input's size)
+  --work-width <px>              Optimise on a copy of the target shrunk to this width
+  --genome <path>                Also save the final genome, to render again at any size
+  --seed <n>                     Seed the random generator for a repeatable run, including
+                                 islands, tiles and batch targets (default: from entropy)
+  --algorithm <name>             strongest (native ES, default), es, sa, de, ga, cmaes, pso
+                                 or nsga2
+  --max-iterations <n>           Iteration limit, ignored by strongest (default: 100000)
+  --target-accuracy <x>          Stop at this accuracy, ignored by strongest (default: 0.95)
+  --stop <rule>                  Stop rule for every algorithm, replacing the two limits above.
+                                 Terms iterations=N, accuracy=X, time=SECONDS, evaluations=N
+                                 and plateau=GAIN/EVALUATIONS joined by & (and) or | (or),
+                                 e.g. \"time=300 | accuracy=0.97 | plateau=0.0005/50000\"
+  --metric <name>                sad or mse (default: sad)
+  --edge-weight <x>              Share of the score, 0 to 1, given to matching the target's
+                                 Sobel edges rather than its pixels (default: 0). Not
+                                 supported by strongest, which scores pixels only
+  --mutation-weight <name>=<w>   Relative weight of a mutation operator (repeatable)
+                                 Operators: move-point, change-colour, move-polygon,
+                                 reorder-polygon, add-polygon, remove-polygon,
+                                 new-point, delete-point, solve-colour (off by default;
+                                 sets a polygon's colour to its least-squares optimum;
+                                 not supported by strongest)
+  --point-delta <px>             Maximum vertex move per mutation (default: 5)
+  --polygon-delta <px>           Maximum polygon move per mutation (default: 3)
+  --colour-delta <n>             Maximum colour channel change per mutation (default: 20)
+  --min-polygons <n>             Never remove polygons below this count (default: 10)
+  --perturbation <kind>          uniform, gaussian or cauchy (default: uniform)
+  --relative-steps               Treat point/polygon deltas as fractions of the longest side;
+                                 their defaults become 0.02 and 0.01
+  --palette <path>               Only use colours from a GIMP palette or a list of hex colours
+  --palette-size <n>             Only use n colours extracted from the target
+  --palette-method <kind>        kmeans or median-cut (default: kmeans)
+  --polygon-shape <kind>         any, simple (no crossing edges) or convex (default: any)
+  --max-vertices <n>             Vertex limit per polygon, at least 3 (default: unlimited)
+  --step-adaptation <rule>       fixed, one-fifth or self-adaptive (default: fixed)
+  --simplify <tolerance>         Prune polygons and vertices of the final genome whose removal
+                                 costs at most this much accuracy each, e.g. 0.0001
+  --merge-distance <px>          Vertex distance for merging near-duplicate polygons when
+                                 simplifying (default: 2)
+  --solve-colours <sweeps>       Least-squares fit every polygon colour of the final genome
+  --restart <strategy>           perturb, random, reheat or ipop (default: per algorithm)
+  --stagnation <n>               Non-improving iterations before a restart (default: per strategy)
+  --max-restarts <n>             Total restart budget (default: unlimited)
+  --hall-of-fame <n>             Best genomes kept across restarts (default: 5)
+  --snapshots <dir>              Save the best genome as a PNG in this directory during the run
+  --snapshot-interval <n>        Iterations between snapshots (default: 1000)
+  --metrics-log <path>           Write per-iteration metrics to this file, or - for stdout
+                                 (replaces the progress lines)
+  --metrics-format <kind>        csv or jsonl (default: from the file extension, else csv)
+  --metrics-interval <n>         Iterations between metrics records (default: 1)
+  --record <path>                Save a time-lapse of the best genome as an animation
+  --record-format <kind>         gif or apng (default: apng for .png/.apng paths, else gif)
+  --record-interval <n>          Iterations between frames (default: 100)
+  --record-on-improvement        Capture a frame whenever the best score improves instead
+  --fps <n>                      Animation frame rate (default: 10)
+  --max-frames <n>               Frame limit; older frames are thinned to fit (default: 200)
+
+GA options:
+  --population-size <n>          Population size (default: 30)
+  --selection <kind>             tournament or rank (default: tournament)
+  --tournament-size <n>          Individuals per tournament (default: 3)
+  --crossover <kind>             uniform, one-point, two-point or spatial (default: uniform)
+  --crossover-rate <x>           Chance a child is bred by crossover (default: 0.7)
+  --mutation-rate <x>            Chance a child is mutated (default: 0.9)
+  --elitism <n>                  Best individuals carried over each generation (default: 2)
+  --steady-state                 Replace one individual per step instead of a generation
+
+CMA-ES options:
+  --cma-sigma <x>                Initial step size in normalised units (default: 0.05)
+  --cma-population <n>           Samples per generation, 0 for automatic (default: 0)
+  --cma-covariance <kind>        full or diagonal (default: diagonal)
+  --cma-refine <generations>     Refine the final genome with CMA-ES after any algorithm
+
+PSO options:
+  --swarm-size <n>               Number of particles (default: 20)
+  --inertia <x>                  Weight of the previous velocity (default: 0.729)
+  --cognitive <x>                Pull towards each particle's best (default: 1.494)
+  --social <x>                   Pull towards the swarm's best (default: 1.494)
+  --max-velocity <x>             Speed limit as a fraction of the canvas/colour range (default: 0.1)
+
+NSGA-II options:
+  --nsga2-population <n>         Population size (default: 40)
+  --complexity <kind>            Size objective: vertices or polygons (default: vertices)
+  --quality-threshold <x>        Output the smallest front genome with at least this accuracy
+                                 (default: the most accurate genome)
+  --front-dir <dir>              Save every Pareto front genome as a PNG in this directory
+
+Island model options:
+  --islands <n>                  Run n copies of --algorithm on separate threads
+  --island-algorithms <list>     Comma-separated algorithm per island, e.g. es,ga,sa
+  --migration-interval <n>       Iterations between migrations (default: 100)
+  --migration-topology <kind>    ring or full (default: ring)
+
+Tiling options:
+  --tiles <px>                   Optimise tiles of this size separately and merge them
+  --tile-overlap <px>            Context each tile sees beyond its edges (default: 16)
+  --tile-workers <n>             Tiles optimised in parallel (default: available cores)
+  --tile-refine <iterations>     Refine the merged genome over the whole image (default: 0)
+
+Benchmark options:
+  --targets <list>               Comma-separated target images (default: the bundled icons)
+  --algorithms <list>            Comma-separated algorithms, including strongest
+                                 (default: strongest,es,sa,de,ga,cmaes,pso)
+  --metrics <list>               Comma-separated metrics to optimise (default: sad)
+  --variant <label>=<options>    Extra configuration to compare, e.g. \"big=--population-size 60\"
+                                 (repeatable; default: the options given on the command line)
+  --seeds <n>                    Runs per case and target, seeded 0..n (default: 3)
+  --evaluations <n>              Fitness evaluations per run (default: 5000)
+  --csv <path>                   Write the summary table as CSV
+  --json <path>                  Write summaries and individual runs as JSON
+
+Sequence options:
+  --input <path>                 Animated GIF or directory of frame images, in file name order
+  --output <dir>                 Directory for per-frame PNGs and genomes and the animation
+                                 (default: ./resources/sequence)
+  --temporal-weight <x>          Penalty on polygon movement from the previous frame (default: 0)
+  --record-format, --fps         Format and frame rate of the output animation
+
+Batch options:
+  --input <dir or glob>          Directory searched recursively, or a glob such as \"photos/**/*.jpg\"
+  --output <dir>                 Root of the mirrored output tree (default: ./resources/batch)
+  --workers <n>                  Targets approximated in parallel (default: available cores)
+  --metrics-format, --metrics-interval
+                                 Format and interval of each target's metrics log
+                                 (default: csv every 100 iterations)
+
+  --help                         Show this message";
+
+#[cfg(feature = "cli")]
+const BENCHMARK_TARGETS: [&str; 8] = [
+    "apple", "firefox", "github", "linux", "rust", "spotify", "twitter", "youtube",
+];
+
+#[cfg(feature = "cli")]
+#[derive(Clone)]
+struct BenchmarkOptions {
+    targets: Vec<String>,
+    algorithms: Vec<BenchmarkAlgorithm>,
+    metrics: Vec<Metric>,
+    /// Labelled option lists applied on top of the command line options
+    variants: Vec<(String, Vec<String>)>,
+    seeds: u64,
+    evaluations: usize,
+    csv: Option<String>,
+    json: Option<String>,
+}
+
+#[cfg(feature = "cli")]
+impl Default for BenchmarkOptions {
+    fn default() -> Self {
+        Self {
+            targets: BENCHMARK_TARGETS
+                .iter()
+                .map(|name| format!("./resources/{}.png", name))
+                .collect(),
+            algorithms: vec![
+                BenchmarkAlgorithm::Strongest,
+                BenchmarkAlgorithm::Shared(AlgorithmType::EvolutionStrategy),
+                BenchmarkAlgorithm::Shared(AlgorithmType::SimulatedAnnealing),
+                BenchmarkAlgorithm::Shared(AlgorithmType::DifferentialEvolution),
+                BenchmarkAlgorithm::Shared(AlgorithmType::GeneticAlgorithm),
+                BenchmarkAlgorithm::Shared(AlgorithmType::CmaEs),
+                BenchmarkAlgorithm::Shared(AlgorithmType::ParticleSwarm),
+            ],
+            metrics: vec![Metric::Sad],
+            variants: Vec::new(),
+            seeds: 3,
+            evaluations: 5000,
+            csv: None,
+            json: None,
+        }
+    }
+}
 
 #[cfg(feature = "cli")]
-use image::ImageReader;
+#[derive(Clone)]
+struct CliOptions {
+    input: String,
+    output: String,
+    /// None runs the native `strongest_mutates_alg`
+    algorithm: Option<AlgorithmType>,
+    config: AlgorithmConfig,
+    /// Tournament size given by --tournament-size, which rank selection
+    /// can't use
+    tournament_size: Option<usize>,
+    /// CMA-ES generations to refine the final genome with, 0 to skip
+    cma_refine: usize,
+    /// Colour solving sweeps over the final genome, 0 to skip
+    solve_colours: usize,
+    /// Colours to extract from the target as a palette
+    palette_size: Option<usize>,
+    palette_method: Quantizer,
+    /// Score loss tolerated per simplification step; None to skip
+    simplify: Option<f32>,
+    merge_distance: f32,
+    /// Algorithm per island; empty runs a single optimiser
+    islands: Vec<AlgorithmType>,
+    island_count: usize,
+    migration_interval: usize,
+    migration_topology: MigrationTopology,
+    /// Tile side for tiled approximation, 0 to optimise the whole image
+    tile_size: usize,
+    tile_overlap: usize,
+    tile_workers: usize,
+    tile_refine: usize,
+    /// Minimum accuracy when picking a genome from the NSGA-II front
+    quality_threshold: Option<f32>,
+    front_dir: Option<String>,
+    /// Size to render the result at; None keeps the input's size
+    output_size: Option<(u32, u32)>,
+    /// Width of the downscaled target to optimise against
+    work_width: Option<u32>,
+    genome: Option<String>,
+    seed: Option<u64>,
+    /// Set by the `render` subcommand
+    render: bool,
+    /// Directory for periodic snapshots of the best genome
+    snapshots: Option<String>,
+    snapshot_interval: usize,
+    /// Metrics log path, "-" for stdout
+    metrics_log: Option<String>,
+    metrics_format: Option<MetricsFormat>,
+    metrics_interval: usize,
+    /// Animation path; the format is left unset until parsing finishes
+    record: Option<String>,
+    record_format: Option<AnimationFormat>,
+    recorder: RecorderConfig,
+    /// Set by the `benchmark` subcommand
+    benchmark: Option<BenchmarkOptions>,
+    /// Set by the `sequence` subcommand: the weight of its temporal penalty
+    sequence: Option<f32>,
+    /// Set by the `batch` subcommand: the number of workers
+    batch: Option<usize>,
+}
+
+#[cfg(feature = "cli")]
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<&str>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("Missing value for {}", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
 
 #[cfg(feature = "cli")]
-use algorithms::strongest_mutates_alg;
+fn parse_list<T>(
+    flag: &str,
+    value: Option<&str>,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<Vec<T>, String> {
+    let list: String = parse_value(flag, value)?;
+    list.split(',')
+        .map(|item| parse(item.trim()).ok_or_else(|| format!("Invalid value for {}: {}", flag, item)))
+        .collect()
+}
+
 #[cfg(feature = "cli")]
-use renderer::render_image;
+fn benchmark_options<'a>(
+    options: &'a mut CliOptions,
+    flag: &str,
+) -> Result<&'a mut BenchmarkOptions, String> {
+    options
+        .benchmark
+        .as_mut()
+        .ok_or_else(|| format!("{} is only valid after the benchmark subcommand", flag))
+}
+
+/// Why the native strongest loop can't run with `config`, if it can't: it
+/// scores pixels only, and its renderer paints polygons without the
+/// blending solve-colour fits colours for
 #[cfg(feature = "cli")]
-use scoring::sad_compare;
+fn strongest_conflict(config: &AlgorithmConfig) -> Option<&'static str> {
+    if config.edge_weight > 0.0 {
+        Some("--edge-weight")
+    } else if config.mutation.weight(MutationType::SolveColour) > 0.0 {
+        Some("the solve-colour mutation")
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "cli")]
+fn parse_args(args: &[String]) -> Result<CliOptions, String> {
+    let mut options = CliOptions {
+        input: "./resources/rust.png".to_string(),
+        output: "./resources/output.png".to_string(),
+        algorithm: None,
+        config: AlgorithmConfig::default(),
+        tournament_size: None,
+        cma_refine: 0,
+        solve_colours: 0,
+        palette_size: None,
+        palette_method: Quantizer::KMeans,
+        simplify: None,
+        merge_distance: SimplifyConfig::default().merge_distance,
+        islands: Vec::new(),
+        island_count: 0,
+        migration_interval: 100,
+        migration_topology: MigrationTopology::Ring,
+        tile_size: 0,
+        tile_overlap: 16,
+        tile_workers: std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1),
+        tile_refine: 0,
+        quality_threshold: None,
+        front_dir: None,
+        output_size: None,
+        work_width: None,
+        genome: None,
+        seed: None,
+        render: false,
+        snapshots: None,
+        snapshot_interval: 1000,
+        metrics_log: None,
+        metrics_format: None,
+        metrics_interval: 1,
+        record: None,
+        record_format: None,
+        recorder: RecorderConfig::default(),
+        benchmark: None,
+        sequence: None,
+        batch: None,
+    };
+
+    let mut args = args;
+    match args.first().map(String::as_str) {
+        Some("benchmark") => {
+            options.benchmark = Some(BenchmarkOptions::default());
+            args = &args[1..];
+        }
+        Some("sequence") => {
+            options.sequence = Some(0.0);
+            options.output = "./resources/sequence".to_string();
+            args = &args[1..];
+        }
+        Some("render") => {
+            options.render = true;
+            args = &args[1..];
+        }
+        Some("batch") => {
+            options.batch = Some(
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1),
+            );
+            options.output = "./resources/batch".to_string();
+            options.metrics_interval = 100;
+            args = &args[1..];
+        }
+        _ => {}
+    }
+    apply_args(&mut options, args)?;
+
+    // --islands n replicates the selected algorithm (ES when using strongest)
+    if options.islands.is_empty() && options.island_count > 0 {
+        let algorithm = options.algorithm.unwrap_or(AlgorithmType::EvolutionStrategy);
+        options.islands = vec![algorithm; options.island_count];
+    }
+    // Islands, tiles and sequences run the shared ES in place of strongest
+    let runs_strongest = match &options.benchmark {
+        Some(benchmark) => benchmark.algorithms.contains(&BenchmarkAlgorithm::Strongest),
+        None => {
+            options.algorithm.is_none()
+                && options.islands.is_empty()
+                && options.tile_size == 0
+                && options.sequence.is_none()
+        }
+    };
+    if let Some(conflict) = strongest_conflict(&options.config).filter(|_| runs_strongest) {
+        let remedy = match options.benchmark {
+            Some(_) => "leave it out of --algorithms",
+            None => "select another --algorithm",
+        };
+        return Err(format!("{} is not supported by strongest; {}", conflict, remedy));
+    }
+    if options.palette_size.is_some() && options.config.mutation.palette.is_some() {
+        return Err("--palette cannot be combined with --palette-size".to_string());
+    }
+    if options.tile_size > 0 && !options.islands.is_empty() {
+        return Err("--tiles cannot be combined with --islands".to_string());
+    }
+
+    // Check variant options up front rather than part way through a benchmark
+    if let Some(benchmark) = &options.benchmark {
+        let runs_strongest = benchmark.algorithms.contains(&BenchmarkAlgorithm::Strongest);
+        for (label, variant_args) in &benchmark.variants {
+            let mut variant = options.clone();
+            apply_args(&mut variant, variant_args)
+                .map_err(|err| format!("In variant {}: {}", label, err))?;
+            if let Some(conflict) = strongest_conflict(&variant.config).filter(|_| runs_strongest) {
+                return Err(format!(
+                    "In variant {}: {} is not supported by strongest; leave it out of --algorithms",
+                    label, conflict
+                ));
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+#[cfg(feature = "cli")]
+fn apply_args(options: &mut CliOptions, args: &[String]) -> Result<(), String> {
+    let mut iter = args.iter().map(String::as_str);
+    while let Some(flag) = iter.next() {
+        match flag {
+            "--input" => options.input = parse_value(flag, iter.next())?,
+            "--output" => options.output = parse_value(flag, iter.next())?,
+            "--algorithm" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.algorithm = match name.as_str() {
+                    "strongest" => None,
+                    _ => Some(
+                        AlgorithmType::from_name(&name)
+                            .ok_or_else(|| format!("Unknown algorithm: {}", name))?,
+                    ),
+                };
+            }
+            "--max-iterations" => options.config.max_iterations = parse_value(flag, iter.next())?,
+            "--target-accuracy" => {
+                options.config.target_accuracy = parse_value(flag, iter.next())?
+            }
+            "--stop" => {
+                let spec: String = parse_value(flag, iter.next())?;
+                options.config.stopping = Some(StopCondition::parse(&spec)?);
+            }
+            "--metric" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.config.metric =
+                    Metric::from_name(&name).ok_or_else(|| format!("Unknown metric: {}", name))?;
+            }
+            "--edge-weight" => {
+                let weight: f32 = parse_value(flag, iter.next())?;
+                if !(0.0..=1.0).contains(&weight) {
+                    return Err("--edge-weight must be between 0 and 1".to_string());
+                }
+                options.config.edge_weight = weight;
+            }
+            "--population-size" => {
+                options.config.ga_population_size = parse_value(flag, iter.next())?
+            }
+            "--selection" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.config.ga_selection = match Selection::from_name(&name) {
+                    Some(Selection::Rank) if options.tournament_size.is_some() => {
+                        return Err("--tournament-size cannot be combined with --selection rank".to_string())
+                    }
+                    // Keep a tournament size given earlier on the command line
+                    Some(Selection::Tournament { size }) => Selection::Tournament {
+                        size: options.tournament_size.unwrap_or(size),
+                    },
+                    Some(selection) => selection,
+                    None => return Err(format!("Unknown selection: {}", name)),
+                };
+            }
+            "--tournament-size" => {
+                if options.config.ga_selection == Selection::Rank {
+                    return Err("--tournament-size cannot be combined with --selection rank".to_string());
+                }
+                let size = parse_value(flag, iter.next())?;
+                options.tournament_size = Some(size);
+                options.config.ga_selection = Selection::Tournament { size };
+            }
+            "--crossover" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.config.ga_crossover_type = CrossoverType::from_name(&name)
+                    .ok_or_else(|| format!("Unknown crossover: {}", name))?;
+            }
+            "--crossover-rate" => {
+                options.config.ga_crossover_rate = parse_value(flag, iter.next())?
+            }
+            "--mutation-rate" => options.config.ga_mutation_rate = parse_value(flag, iter.next())?,
+            "--elitism" => options.config.ga_elitism = parse_value(flag, iter.next())?,
+            "--steady-state" => options.config.ga_replacement = Replacement::SteadyState,
+            "--cma-sigma" => options.config.cma.sigma = parse_value(flag, iter.next())?,
+            "--cma-population" => {
+                options.config.cma.population_size = parse_value(flag, iter.next())?
+            }
+            "--cma-covariance" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.config.cma.covariance = Covariance::from_name(&name)
+                    .ok_or_else(|| format!("Unknown covariance model: {}", name))?;
+            }
+            "--cma-refine" => options.cma_refine = parse_value(flag, iter.next())?,
+            "--solve-colours" => options.solve_colours = parse_value(flag, iter.next())?,
+            "--simplify" => options.simplify = Some(parse_value(flag, iter.next())?),
+            "--merge-distance" => options.merge_distance = parse_value(flag, iter.next())?,
+            "--islands" => options.island_count = parse_value(flag, iter.next())?,
+            "--island-algorithms" => {
+                let list: String = parse_value(flag, iter.next())?;
+                options.islands = list
+                    .split(',')
+                    .map(|name| {
+                        AlgorithmType::from_name(name.trim())
+                            .ok_or_else(|| format!("Unknown algorithm: {}", name))
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            "--migration-interval" => options.migration_interval = parse_value(flag, iter.next())?,
+            "--migration-topology" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.migration_topology = MigrationTopology::from_name(&name)
+                    .ok_or_else(|| format!("Unknown migration topology: {}", name))?;
+            }
+            "--tiles" => options.tile_size = parse_value(flag, iter.next())?,
+            "--tile-overlap" => options.tile_overlap = parse_value(flag, iter.next())?,
+            "--tile-workers" => options.tile_workers = parse_value(flag, iter.next())?,
+            "--tile-refine" => options.tile_refine = parse_value(flag, iter.next())?,
+            "--swarm-size" => options.config.pso.swarm_size = parse_value(flag, iter.next())?,
+            "--inertia" => options.config.pso.inertia = parse_value(flag, iter.next())?,
+            "--cognitive" => options.config.pso.cognitive = parse_value(flag, iter.next())?,
+            "--social" => options.config.pso.social = parse_value(flag, iter.next())?,
+            "--max-velocity" => options.config.pso.max_velocity = parse_value(flag, iter.next())?,
+            "--nsga2-population" => {
+                options.config.nsga2.population_size = parse_value(flag, iter.next())?
+            }
+            "--complexity" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.config.nsga2.complexity = Complexity::from_name(&name)
+                    .ok_or_else(|| format!("Unknown complexity measure: {}", name))?;
+            }
+            "--quality-threshold" => {
+                options.quality_threshold = Some(parse_value(flag, iter.next())?)
+            }
+            "--front-dir" => options.front_dir = Some(parse_value(flag, iter.next())?),
+            "--output-size" => {
+                let spec: String = parse_value(flag, iter.next())?;
+                let (width, height) = spec
+                    .split_once('x')
+                    .ok_or_else(|| format!("Expected <width>x<height>, got {}", spec))?;
+                options.output_size = Some((
+                    parse_value(flag, Some(width))?,
+                    parse_value(flag, Some(height))?,
+                ));
+            }
+            "--work-width" => options.work_width = Some(parse_value(flag, iter.next())?),
+            "--genome" => options.genome = Some(parse_value(flag, iter.next())?),
+            "--seed" => options.seed = Some(parse_value(flag, iter.next())?),
+            "--snapshots" => options.snapshots = Some(parse_value(flag, iter.next())?),
+            "--snapshot-interval" => options.snapshot_interval = parse_value(flag, iter.next())?,
+            "--metrics-log" => options.metrics_log = Some(parse_value(flag, iter.next())?),
+            "--metrics-format" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.metrics_format = Some(
+                    MetricsFormat::from_name(&name)
+                        .ok_or_else(|| format!("Unknown metrics format: {}", name))?,
+                );
+            }
+            "--metrics-interval" => options.metrics_interval = parse_value(flag, iter.next())?,
+            "--record" => options.record = Some(parse_value(flag, iter.next())?),
+            "--record-format" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.record_format = Some(
+                    AnimationFormat::from_name(&name)
+                        .ok_or_else(|| format!("Unknown animation format: {}", name))?,
+                );
+            }
+            "--record-interval" => {
+                options.recorder.capture = Capture::Interval(parse_value(flag, iter.next())?)
+            }
+            "--record-on-improvement" => options.recorder.capture = Capture::OnImprovement,
+            "--fps" => options.recorder.fps = parse_value(flag, iter.next())?,
+            "--max-frames" => options.recorder.max_frames = parse_value(flag, iter.next())?,
+            "--mutation-weight" => {
+                let spec: String = parse_value(flag, iter.next())?;
+                let (name, weight) = spec
+                    .split_once('=')
+                    .ok_or_else(|| format!("Expected <name>=<weight>, got {}", spec))?;
+                let mutation_type = MutationType::from_name(name)
+                    .ok_or_else(|| format!("Unknown mutation operator: {}", name))?;
+                let weight: f32 = parse_value(flag, Some(weight))?;
+                options.config.mutation.set_weight(mutation_type, weight);
+            }
+            "--point-delta" => {
+                options.config.mutation.point_move_delta = check_delta(flag, parse_value(flag, iter.next())?)?
+            }
+            "--polygon-delta" => {
+                options.config.mutation.polygon_move_delta = check_delta(flag, parse_value(flag, iter.next())?)?
+            }
+            "--colour-delta" => {
+                let delta: i16 = parse_value(flag, iter.next())?;
+                options.config.mutation.colour_delta = check_delta(flag, delta as f32)? as i16;
+            }
+            "--min-polygons" => options.config.mutation.min_polygons = parse_value(flag, iter.next())?,
+            "--perturbation" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.config.mutation.perturbation = Perturbation::from_name(&name)
+                    .ok_or_else(|| format!("Unknown perturbation: {}", name))?;
+            }
+            "--relative-steps" => options.config.mutation.set_step_scale(StepScale::Relative),
+            "--palette" => {
+                let path: String = parse_value(flag, iter.next())?;
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|err| format!("Cannot read {}: {}", path, err))?;
+                options.config.mutation.palette =
+                    Some(Palette::parse(&text).map_err(|err| format!("{}: {}", path, err))?);
+            }
+            "--palette-size" => options.palette_size = Some(parse_value(flag, iter.next())?),
+            "--palette-method" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.palette_method = Quantizer::from_name(&name)
+                    .ok_or_else(|| format!("Unknown palette method: {}", name))?;
+            }
+            "--polygon-shape" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.config.mutation.constraints.shape = PolygonShape::from_name(&name)
+                    .ok_or_else(|| format!("Unknown polygon shape: {}", name))?;
+            }
+            "--max-vertices" => {
+                let max: usize = parse_value(flag, iter.next())?;
+                if max < 3 {
+                    return Err(format!("{} must be at least 3", flag));
+                }
+                options.config.mutation.constraints.max_vertices = Some(max);
+            }
+            "--step-adaptation" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.config.mutation.adaptation = StepAdaptation::from_name(&name)
+                    .ok_or_else(|| format!("Unknown step adaptation: {}", name))?;
+            }
+            "--restart" => {
+                let name: String = parse_value(flag, iter.next())?;
+                options.config.restart.strategy = Some(
+                    RestartStrategy::from_name(&name)
+                        .ok_or_else(|| format!("Unknown restart strategy: {}", name))?,
+                );
+            }
+            "--stagnation" => {
+                options.config.restart.stagnation_threshold = Some(parse_value(flag, iter.next())?)
+            }
+            "--max-restarts" => {
+                options.config.restart.max_restarts = Some(parse_value(flag, iter.next())?)
+            }
+            "--hall-of-fame" => {
+                options.config.restart.hall_of_fame_size = parse_value(flag, iter.next())?
+            }
+            "--targets" => {
+                benchmark_options(options, flag)?.targets =
+                    parse_list(flag, iter.next(), |path| Some(path.to_string()))?
+            }
+            "--algorithms" => {
+                benchmark_options(options, flag)?.algorithms =
+                    parse_list(flag, iter.next(), BenchmarkAlgorithm::from_name)?
+            }
+            "--metrics" => {
+                benchmark_options(options, flag)?.metrics =
+                    parse_list(flag, iter.next(), Metric::from_name)?
+            }
+            "--variant" => {
+                let spec: String = parse_value(flag, iter.next())?;
+                let (label, variant_args) = spec
+                    .split_once('=')
+                    .ok_or_else(|| format!("Expected <label>=<options>, got {}", spec))?;
+                let variant_args = variant_args.split_whitespace().map(String::from).collect();
+                benchmark_options(options, flag)?
+                    .variants
+                    .push((label.to_string(), variant_args));
+            }
+            "--seeds" => benchmark_options(options, flag)?.seeds = parse_value(flag, iter.next())?,
+            "--evaluations" => {
+                benchmark_options(options, flag)?.evaluations = parse_value(flag, iter.next())?
+            }
+            "--csv" => benchmark_options(options, flag)?.csv = Some(parse_value(flag, iter.next())?),
+            "--json" => benchmark_options(options, flag)?.json = Some(parse_value(flag, iter.next())?),
+            "--temporal-weight" => {
+                let weight = parse_value(flag, iter.next())?;
+                *options.sequence.as_mut().ok_or_else(|| {
+                    format!("{} is only valid after the sequence subcommand", flag)
+                })? = weight;
+            }
+            "--workers" => {
+                let workers = parse_value(flag, iter.next())?;
+                *options.batch.as_mut().ok_or_else(|| {
+                    format!("{} is only valid after the batch subcommand", flag)
+                })? = workers;
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            other => return Err(format!("Unknown option: {}", other)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Render with the scanline renderer the shared algorithms score against
+#[cfg(feature = "cli")]
+fn render_scanline(image: &Image) -> RgbaImage {
+    let buffer = renderer_wasm::render_image(image);
+    RgbaImage::from_raw(image.width as u32, image.height as u32, buffer.data).unwrap()
+}
+
+/// Console progress, or metrics streamed to stdout in its place, plus any
+/// metrics file and snapshots requested; `render` should be the renderer
+/// the run scores with
+#[cfg(feature = "cli")]
+fn progress_observers(options: &CliOptions, render: fn(&Image) -> RgbaImage) -> Vec<Box<dyn Observer>> {
+    let mut observers: Vec<Box<dyn Observer>> = Vec::new();
+    if options.metrics_log.as_deref() != Some("-") {
+        observers.push(Box::new(ConsoleProgress {
+            interval: PROGRESS_INTERVAL,
+        }));
+    }
+    if let Some(path) = &options.metrics_log {
+        let format = options
+            .metrics_format
+            .unwrap_or_else(|| MetricsFormat::from_path(path));
+        let log = MetricsLog::create(path, format, options.metrics_interval)
+            .unwrap_or_else(|err| panic!("Cannot create {}: {}", path, err));
+        observers.push(Box::new(log));
+    }
+    if let Some(dir) = &options.snapshots {
+        let saver = SnapshotSaver::new(dir, options.snapshot_interval, render)
+            .unwrap_or_else(|err| panic!("Cannot create {}: {}", dir, err));
+        observers.push(Box::new(saver));
+    }
+    if let Some(path) = &options.record {
+        let config = RecorderConfig {
+            format: options
+                .record_format
+                .unwrap_or_else(|| AnimationFormat::from_path(path)),
+            ..options.recorder.clone()
+        };
+        observers.push(Box::new(Recorder::new(path, config, render)));
+    }
+    observers
+}
+
+/// Drive an `AlgorithmState` to completion, reporting to `observer`
+#[cfg(feature = "cli")]
+fn run_algorithm(target: &RgbaImage, config: AlgorithmConfig, observer: &mut dyn Observer) -> AlgorithmState {
+    let (width, height) = target.dimensions();
+    let mut state = AlgorithmState::new(
+        target.as_raw().clone(),
+        width as usize,
+        height as usize,
+        config,
+    );
+
+    state.run_observed(usize::MAX, observer);
+
+    state
+}
+
+/// Renderer the selected algorithm scores with: native for `strongest`
+#[cfg(feature = "cli")]
+fn scoring_renderer(options: &CliOptions) -> fn(&Image) -> RgbaImage {
+    if options.algorithm.is_none() && options.islands.is_empty() && options.tile_size == 0 {
+        render_image
+    } else {
+        render_scanline
+    }
+}
+
+/// Print the NSGA-II Pareto front, optionally saving each genome, and pick
+/// the smallest genome meeting the quality threshold
+#[cfg(feature = "cli")]
+fn choose_from_front(state: &AlgorithmState, options: &CliOptions) -> Image {
+    let front = state.pareto_front();
+    let measure = options.config.nsga2.complexity.name();
+
+    println!("Pareto front ({} genomes):", front.len());
+    for (i, individual) in front.iter().enumerate() {
+        println!(
+            "  {:>5} {}: accuracy = {:.4}%",
+            individual.complexity,
+            measure,
+            individual.accuracy * 100.0
+        );
+        if let Some(dir) = &options.front_dir {
+            std::fs::create_dir_all(dir).unwrap();
+            let path = format!("{}/front_{:03}_{}.png", dir, i, individual.complexity);
+            render_scanline(&individual.image).save(&path).unwrap();
+        }
+    }
+    if let Some(dir) = &options.front_dir {
+        println!("Saved front to {}", dir);
+    }
+
+    let chosen = options
+        .quality_threshold
+        .and_then(|threshold| front.iter().find(|i| i.accuracy >= threshold));
+    match chosen {
+        Some(individual) => {
+            println!(
+                "Chose {} {} at {:.4}% accuracy",
+                individual.complexity,
+                measure,
+                individual.accuracy * 100.0
+            );
+            individual.image.clone()
+        }
+        None => {
+            if let Some(threshold) = options.quality_threshold {
+                println!(
+                    "No front genome reaches {:.4}%; using the most accurate",
+                    threshold * 100.0
+                );
+            }
+            state.best_image().clone()
+        }
+    }
+}
+
+/// Run the island model, printing per-island and global progress
+#[cfg(feature = "cli")]
+fn run_island_model(target: &RgbaImage, options: &CliOptions, observer: &mut dyn Observer) -> Image {
+    let (width, height) = target.dimensions();
+    let config = IslandConfig {
+        islands: options
+            .islands
+            .iter()
+            .map(|algorithm| {
+                let mut config = options.config.clone();
+                config.algorithm_type = *algorithm;
+                config
+            })
+            .collect(),
+        migration_interval: options.migration_interval,
+        topology: options.migration_topology,
+        seed: options.seed,
+    };
+    if options.algorithm.is_none() && options.island_count > 0 {
+        println!("The native strongest loop has no island mode; islands run es");
+    }
+
+    let result = run_islands(
+        target.as_raw(),
+        width as usize,
+        height as usize,
+        config,
+        observer,
+        |islands| {
+            let summary: Vec<String> = islands
+                .iter()
+                .map(|r| format!("#{} {} {:.4}%", r.island, r.algorithm.name(), r.best_score * 100.0))
+                .collect();
+            let global = islands.iter().map(|r| r.best_score).fold(0.0, f32::max);
+            println!(
+                "Iteration {}: global = {:.4}% | {}",
+                islands.iter().map(|r| r.iteration).max().unwrap_or(0),
+                global * 100.0,
+                summary.join(" | ")
+            );
+        },
+    );
+
+    for report in &result.islands {
+        println!(
+            "Island {} ({}): {} iterations, accuracy {:.4}%",
+            report.island,
+            report.algorithm.name(),
+            report.iteration,
+            report.best_score * 100.0
+        );
+    }
+    println!("Global best accuracy {:.4}%", result.best_score * 100.0);
+
+    result.best
+}
+
+/// Optimise the target tile by tile and merge the tiles' genomes
+#[cfg(feature = "cli")]
+fn run_tile_model(target: &RgbaImage, options: &CliOptions, observer: &mut dyn Observer) -> Image {
+    let (width, height) = target.dimensions();
+    // The native loop only runs on whole images, so strongest tiles with the shared ES
+    let mut algorithm = options.config.clone();
+    algorithm.algorithm_type = options.algorithm.unwrap_or(AlgorithmType::EvolutionStrategy);
+    let config = TileConfig {
+        tile_size: options.tile_size,
+        overlap: options.tile_overlap,
+        workers: options.tile_workers,
+        refine_iterations: options.tile_refine,
+        seed: options.seed,
+    };
+
+    let merged = run_tiled(
+        target.as_raw(),
+        width as usize,
+        height as usize,
+        &algorithm,
+        &config,
+        observer,
+        |report| {
+            println!(
+                "Tile {}/{}: accuracy = {:.4}%",
+                report.index + 1,
+                report.tiles,
+                report.accuracy * 100.0
+            );
+        },
+    );
+    println!(
+        "Tiled genome: {} polygons, accuracy = {:.4}%",
+        merged.polygon.len(),
+        options
+            .config
+            .metric
+            .compare_raw(target.as_raw(), &renderer_wasm::render_image(&merged).data)
+            * 100.0
+    );
+    merged
+}
+
+/// Run every variant, algorithm and metric over the targets and report
+#[cfg(feature = "cli")]
+fn run_benchmark_command(options: &CliOptions) {
+    let benchmark = options.benchmark.as_ref().unwrap();
+
+    let targets: Vec<BenchmarkTarget> = benchmark
+        .targets
+        .iter()
+        .map(|path| {
+            let img = ImageReader::open(path)
+                .unwrap_or_else(|err| panic!("Cannot open {}: {}", path, err))
+                .decode()
+                .unwrap()
+                .to_rgba8();
+            BenchmarkTarget {
+                name: std::path::Path::new(path)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.clone()),
+                width: img.width() as usize,
+                height: img.height() as usize,
+                pixels: img.into_raw(),
+            }
+        })
+        .collect();
+
+    // Without variants the command line options form a single configuration
+    let variants: Vec<(Option<&str>, AlgorithmConfig)> = if benchmark.variants.is_empty() {
+        vec![(None, options.config.clone())]
+    } else {
+        benchmark
+            .variants
+            .iter()
+            .map(|(label, args)| {
+                let mut variant = options.clone();
+                apply_args(&mut variant, args).unwrap();
+                (Some(label.as_str()), variant.config)
+            })
+            .collect()
+    };
+
+    let mut cases = Vec::new();
+    for (label, config) in &variants {
+        for algorithm in &benchmark.algorithms {
+            for metric in &benchmark.metrics {
+                let mut config = config.clone();
+                config.metric = *metric;
+                let name = format!("{}/{}", algorithm.name(), metric.name());
+                cases.push(BenchmarkCase {
+                    label: match label {
+                        Some(label) => format!("{}/{}", label, name),
+                        None => name,
+                    },
+                    algorithm: *algorithm,
+                    config,
+                });
+            }
+        }
+    }
+
+    let config = BenchmarkConfig {
+        cases,
+        targets,
+        seeds: (0..benchmark.seeds).collect(),
+        evaluation_budget: benchmark.evaluations,
+    };
+
+    let results = run_benchmark(&config, |run| {
+        println!(
+            "{} on {} (seed {}): accuracy = {:.4}%, {} evaluations in {:.2}s",
+            run.case,
+            run.target,
+            run.seed,
+            run.accuracy * 100.0,
+            run.evaluations,
+            run.seconds
+        );
+    });
+    let summaries = summarise(&results);
+
+    println!();
+    print!("{}", format_table(&summaries));
+    if let Some(note) = budget_note(&results, config.evaluation_budget) {
+        println!("Note: {}", note);
+    }
+
+    if let Some(path) = &benchmark.csv {
+        std::fs::write(path, summaries_to_csv(&summaries)).unwrap();
+        println!("Saved CSV to {}", path);
+    }
+    if let Some(path) = &benchmark.json {
+        std::fs::write(path, to_json(&summaries, &results)).unwrap();
+        println!("Saved JSON to {}", path);
+    }
+}
+
+/// Approximate every frame of a sequence, saving each frame's render and
+/// genome and an animation of the results
+#[cfg(feature = "cli")]
+fn run_sequence_command(options: &CliOptions) {
+    let frames = load_frames(std::path::Path::new(&options.input)).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    println!("Loaded {} frames from {}", frames.len(), options.input);
+
+    // The native loop has no warm start, so strongest runs the shared ES
+    let mut algorithm = options.config.clone();
+    algorithm.algorithm_type = options.algorithm.unwrap_or(AlgorithmType::EvolutionStrategy);
+    // One palette for the whole sequence keeps colours stable between frames
+    if let Some(palette) = target_palette(&frames[0], options) {
+        algorithm.mutation.palette = Some(palette);
+    }
+    let config = SequenceConfig {
+        algorithm,
+        temporal_weight: options.sequence.unwrap_or(0.0),
+    };
+
+    let dir = std::path::Path::new(&options.output);
+    std::fs::create_dir_all(dir).unwrap();
+
+    let mut observers = progress_observers(options, render_scanline);
+    let mut rendered = Vec::with_capacity(frames.len());
+    run_sequence(&frames, &config, &mut observers, |index, result| {
+        println!(
+            "Frame {}: accuracy = {:.4}%, displacement = {:.4}, {} evaluations",
+            index,
+            result.accuracy * 100.0,
+            result.displacement,
+            result.evaluations
+        );
+        let render = render_scanline(&result.genome);
+        render.save(dir.join(format!("{:05}.png", index))).unwrap();
+        std::fs::write(dir.join(format!("{:05}.genome", index)), genome::to_text(&result.genome))
+            .unwrap();
+        rendered.push(render);
+    });
+
+    let format = options.record_format.unwrap_or(AnimationFormat::Gif);
+    let extension = match format {
+        AnimationFormat::Gif => "gif",
+        AnimationFormat::Apng => "png",
+    };
+    let animation = dir.join(format!("animation.{}", extension));
+    write_animation(&animation, &rendered, format, options.recorder.fps).unwrap();
+    println!("Saved frames and animation to {}", dir.display());
+}
+
+/// The palette `--palette-size` asks for, extracted from `target`
+#[cfg(feature = "cli")]
+fn target_palette(target: &RgbaImage, options: &CliOptions) -> Option<Palette> {
+    let size = options.palette_size?;
+    let palette = Palette::from_target(target.as_raw(), size, options.palette_method);
+    println!("Palette of {} colours: {}", palette.colours.len(), palette.to_hex());
+    Some(palette)
+}
+
+/// Run the selected optimiser on one target, then any CMA-ES refinement
+/// and colour solving. Returns the genome and whether to render it with
+/// the native renderer it was scored with.
+#[cfg(feature = "cli")]
+fn approximate(img: RgbaImage, options: &CliOptions, observer: &mut dyn Observer) -> (Image, bool) {
+    let with_palette;
+    let options = match target_palette(&img, options) {
+        Some(palette) => {
+            let mut extended = options.clone();
+            extended.config.mutation.palette = Some(palette);
+            with_palette = extended;
+            &with_palette
+        }
+        None => options,
+    };
+    let target_pixels = img.as_raw().clone();
+    let cma_config = options.config.cma.clone();
+    let compare_fn = match options.config.metric {
+        Metric::Sad => sad_compare,
+        Metric::Mse => mse_compare,
+    };
+    // Scores the post-passes below by the same measure and renderer the
+    // search used, which for the native loop is imageproc's
+    let evaluator = Evaluator::new(target_pixels.clone(), options.config.metric)
+        .with_temporal_penalty(options.config.temporal.clone())
+        .with_edge_weight(options.config.edge_weight, img.width() as usize, img.height() as usize);
+    let target = img.clone();
+    let score_with = |img: &Image, native_render: bool| {
+        if native_render {
+            compare_fn(&target, &render_image(img))
+        } else {
+            evaluator.score(img)
+        }
+    };
+
+    let (result, native_render) = match options.algorithm {
+        _ if !options.islands.is_empty() => (run_island_model(&img, options, observer), false),
+        _ if options.tile_size > 0 => (run_tile_model(&img, options, observer), false),
+        None => (
+            strongest_mutates_alg(
+                img,
+                compare_fn,
+                &options.config.mutation,
+                &options.config.restart,
+                options.config.stopping.as_ref(),
+                observer,
+            ),
+            true,
+        ),
+        Some(algorithm_type) => {
+            let mut config = options.config.clone();
+            config.algorithm_type = algorithm_type;
+            let state = run_algorithm(&img, config, observer);
+            let best = if algorithm_type == AlgorithmType::Nsga2 {
+                choose_from_front(&state, options)
+            } else {
+                state.best_image().clone()
+            };
+            (best, false)
+        }
+    };
+
+    let (mut result, mut native_render) = (result, native_render);
+    if options.cma_refine > 0 {
+        // The refined genome is rendered by the scanline renderer
+        let (score, refined) =
+            refine_with_cmaes(&result, &cma_config, options.cma_refine, |img| score_with(img, false));
+        println!("CMA-ES refinement: accuracy = {:.4}%", score * 100.0);
+        result = refined;
+        native_render = false;
+    }
+    let score = |img: &Image| score_with(img, native_render);
+
+    // Fixed-topology searches and CMA-ES refinement move vertices and
+    // colours freely
+    let constraints = options.config.mutation.constraints;
+    let (repaired, count) = constraints.repair(result.clone());
+    let (repaired, recoloured) = match &options.config.mutation.palette {
+        Some(palette) => palette.snap(repaired),
+        None => (repaired, 0),
+    };
+    if count + recoloured > 0 {
+        println!(
+            "Repaired {} and recoloured {} of {} polygons: accuracy = {:.4}% -> {:.4}%",
+            count,
+            recoloured,
+            repaired.polygon.len(),
+            score(&result) * 100.0,
+            score(&repaired) * 100.0
+        );
+        result = repaired;
+    }
+
+    if let Some(tolerance) = options.simplify {
+        let config = SimplifyConfig {
+            tolerance,
+            merge_distance: options.merge_distance,
+            constraints,
+            palette: options.config.mutation.palette.clone(),
+        };
+        let (simplified, report) = simplify(result, &config, score);
+        println!("Simplified: {}", report.summary());
+        result = simplified;
+    }
+
+    if options.solve_colours > 0 {
+        let before = score(&result);
+        let solved = options.config.mutation.conform(solve_all_colours(
+            result.clone(),
+            &target_pixels,
+            options.solve_colours,
+        ));
+        let after = score(&solved);
+        // The fit is least squares, so under SAD it can occasionally lose
+        if after > before {
+            result = solved;
+        }
+        println!(
+            "Colour solving: accuracy = {:.4}% -> {:.4}%",
+            before * 100.0,
+            after.max(before) * 100.0
+        );
+    }
+
+    (result, native_render)
+}
+
+/// Render a saved genome, at `--output-size` if given
+#[cfg(feature = "cli")]
+fn run_render_command(options: &CliOptions) {
+    let text = std::fs::read_to_string(&options.input).unwrap_or_else(|err| {
+        eprintln!("Cannot read {}: {}", options.input, err);
+        std::process::exit(1);
+    });
+    let genome = genome::from_text(&text).unwrap_or_else(|err| {
+        eprintln!("{}: {}", options.input, err);
+        std::process::exit(1);
+    });
+    let (genome, repaired) = options.config.mutation.constraints.repair(genome);
+    if repaired > 0 {
+        println!("Repaired {} of {} polygons", repaired, genome.polygon.len());
+    }
+    let genome = match &options.config.mutation.palette {
+        Some(palette) => {
+            let (genome, recoloured) = palette.snap(genome);
+            if recoloured > 0 {
+                println!("Recoloured {} of {} polygons", recoloured, genome.polygon.len());
+            }
+            genome
+        }
+        None => genome,
+    };
+
+    let (width, height) = options
+        .output_size
+        .map(|(w, h)| (w as usize, h as usize))
+        .unwrap_or((genome.width, genome.height));
+    render_scanline(&genome.rescaled(width, height))
+        .save(&options.output)
+        .unwrap();
+    println!("Saved {}x{} render to {}", width, height, options.output);
+}
+
+/// Approximate one batch target, writing its render, metrics and genome
+#[cfg(feature = "cli")]
+fn approximate_job(job: &BatchJob, options: &CliOptions) -> Result<JobOutcome, String> {
+    let img = ImageReader::open(&job.input)
+        .map_err(|err| err.to_string())?
+        .decode()
+        .map_err(|err| err.to_string())?
+        .to_rgba8();
+    // Workers take targets in any order, so each is seeded from its path
+    if let Some(seed) = options.seed {
+        let stream = job
+            .input
+            .to_string_lossy()
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3));
+        random::seed(derive_seed(seed, stream));
+    }
+    if let Some(parent) = job.output.parent() {
+        std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    let target_pixels = img.as_raw().clone();
+
+    let format = options.metrics_format.unwrap_or(MetricsFormat::Csv);
+    let metrics = MetricsLog::create(
+        &job.with_extension(&format!("metrics.{}", format.name())).to_string_lossy(),
+        format,
+        options.metrics_interval,
+    )
+    .map_err(|err| err.to_string())?;
+    let mut last = (0, 0);
+    let mut observers: Vec<Box<dyn Observer + '_>> = vec![
+        Box::new(metrics),
+        Box::new(|status: &RunStatus| {
+            last = (status.iteration, status.evaluations);
+            Control::Continue
+        }),
+    ];
+
+    let (result, native_render) = approximate(img, options, &mut observers);
+    drop(observers);
+
+    let rendered = if native_render {
+        render_image(&result)
+    } else {
+        render_scanline(&result)
+    };
+    rendered
+        .save(job.with_extension("png"))
+        .map_err(|err| err.to_string())?;
+    // Written last: its presence marks the target complete
+    std::fs::write(job.with_extension("genome"), genome::to_text(&result))
+        .map_err(|err| err.to_string())?;
+
+    Ok(JobOutcome {
+        accuracy: options.config.metric.compare_raw(&target_pixels, rendered.as_raw()),
+        iterations: last.0,
+        evaluations: last.1,
+    })
+}
+
+/// Accuracy of a completed batch target's saved render, which used the
+/// renderer of whichever algorithm produced it
+#[cfg(feature = "cli")]
+fn remeasure_job(job: &BatchJob, options: &CliOptions) -> Option<f32> {
+    let target = ImageReader::open(&job.input).ok()?.decode().ok()?.to_rgba8();
+    let rendered = ImageReader::open(job.with_extension("png")).ok()?.decode().ok()?.to_rgba8();
+    Some(options.config.metric.compare_raw(target.as_raw(), rendered.as_raw()))
+}
+
+/// Approximate every target of a directory or glob into a mirrored output
+/// tree, skipping targets finished by an earlier run
+#[cfg(feature = "cli")]
+fn run_batch_command(options: &CliOptions) {
+    let output = std::path::Path::new(&options.output);
+    let jobs = find_jobs(&options.input, output).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    let workers = options.batch.unwrap_or(1);
+    println!("Approximating {} targets with {} workers", jobs.len(), workers);
+
+    let reports = run_batch(
+        &jobs,
+        workers,
+        |job| approximate_job(job, options),
+        |job| remeasure_job(job, options),
+        |report| match &report.status {
+            JobStatus::Done(outcome) => println!(
+                "{}: accuracy = {:.4}% in {:.1}s",
+                report.input.display(),
+                outcome.accuracy * 100.0,
+                report.seconds
+            ),
+            JobStatus::Skipped(_) => println!("{}: already done, skipped", report.input.display()),
+            JobStatus::Failed(err) => eprintln!("{}: failed: {}", report.input.display(), err),
+        },
+    );
+
+    std::fs::create_dir_all(output).unwrap();
+    let summary = output.join("summary.csv");
+    std::fs::write(&summary, summary_csv(&reports)).unwrap();
+    println!("{}", summary_line(&reports));
+    println!("Saved summary to {}", summary.display());
+}
 
 #[cfg(feature = "cli")]
 fn main() {
-    let img = ImageReader::open("./resources/rust.png")
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(seed) = options.seed {
+        random::seed(seed);
+    }
+    if options.benchmark.is_some() {
+        run_benchmark_command(&options);
+        return;
+    }
+    if options.sequence.is_some() {
+        run_sequence_command(&options);
+        return;
+    }
+
+    if options.batch.is_some() {
+        run_batch_command(&options);
+        return;
+    }
+    if options.render {
+        run_render_command(&options);
+        return;
+    }
+
+    let img = ImageReader::open(&options.input)
         .unwrap()
         .decode()
         .unwrap()
         .to_rgba8();
+    let (width, height) = img.dimensions();
+
+    let img = match options.work_width {
+        Some(work_width) if work_width < width => {
+            let work_height = (height as u64 * work_width as u64 / width as u64).max(1) as u32;
+            println!("Optimising at {}x{}", work_width, work_height);
+            image::imageops::resize(&img, work_width.max(1), work_height, FilterType::Triangle)
+        }
+        _ => img,
+    };
+
+    let mut observers = progress_observers(&options, scoring_renderer(&options));
+    let (result, native_render) = approximate(img, &options, &mut observers);
+
+    if let Some(path) = &options.genome {
+        std::fs::write(path, genome::to_text(&result)).unwrap();
+        println!("Saved genome to {}", path);
+    }
 
-    let result = strongest_mutates_alg(img, sad_compare);
+    // Genomes scale with the canvas, so a small working copy renders at full size
+    let (output_width, output_height) = options.output_size.unwrap_or((width, height));
+    let (output_width, output_height) = (output_width as usize, output_height as usize);
+    let rendered = if native_render {
+        render_image_at(&result, output_width, output_height)
+    } else {
+        render_scanline(&result.rescaled(output_width, output_height))
+    };
 
     // Save the result
-    let rendered = render_image(&result);
-    rendered.save("./resources/output.png").unwrap();
+    rendered.save(&options.output).unwrap();
 
-    println!("Saved result to ./resources/output.png");
+    println!("Saved result to {}", options.output);
+    if let Some(path) = &options.record {
+        println!("Saved animation to {}", path);
+    }
 }
 
 #[cfg(not(feature = "cli"))]
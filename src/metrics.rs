@@ -0,0 +1,146 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::observer::{Control, Observer, RunStatus};
+
+const CSV_HEADER: &str = "iteration,evaluations,elapsed_seconds,best_score,current_score,polygons,vertices,temperature,accepted_mutation";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricsFormat {
+  Csv,
+  /// One JSON object per line
+  JsonLines,
+}
+
+impl MetricsFormat {
+  pub fn name(self) -> &'static str {
+    match self {
+      MetricsFormat::Csv => "csv",
+      MetricsFormat::JsonLines => "jsonl",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<MetricsFormat> {
+    match name {
+      "csv" => Some(MetricsFormat::Csv),
+      "jsonl" => Some(MetricsFormat::JsonLines),
+      _ => None,
+    }
+  }
+
+  /// JSON Lines for .jsonl/.json paths, CSV otherwise
+  pub fn from_path(path: &str) -> MetricsFormat {
+    if path.ends_with(".jsonl") || path.ends_with(".json") {
+      MetricsFormat::JsonLines
+    } else {
+      MetricsFormat::Csv
+    }
+  }
+}
+
+/// Writes one record per logged iteration, plus a final record when the
+/// run ends, for plotting convergence
+pub struct MetricsLog {
+  out: Box<dyn Write>,
+  format: MetricsFormat,
+  interval: usize,
+  last_logged: Option<usize>,
+  failed: bool,
+}
+
+impl MetricsLog {
+  /// Log every `interval` iterations to `out`, writing the CSV header now
+  pub fn new(mut out: Box<dyn Write>, format: MetricsFormat, interval: usize) -> io::Result<Self> {
+    if format == MetricsFormat::Csv {
+      writeln!(out, "{}", CSV_HEADER)?;
+    }
+    Ok(Self {
+      out,
+      format,
+      interval: interval.max(1),
+      last_logged: None,
+      failed: false,
+    })
+  }
+
+  /// Log to a file, or stream to stdout when `path` is "-"
+  pub fn create(path: &str, format: MetricsFormat, interval: usize) -> io::Result<Self> {
+    let out: Box<dyn Write> = if path == "-" {
+      Box::new(io::stdout())
+    } else {
+      Box::new(BufWriter::new(File::create(path)?))
+    };
+    Self::new(out, format, interval)
+  }
+
+  fn record(&self, status: &RunStatus) -> String {
+    let polygons = status.best.polygon.len();
+    let vertices: usize = status.best.polygon.iter().map(|p| p.points.len()).sum();
+    let mutation = status.accepted_mutation.map(|m| m.name());
+
+    match self.format {
+      MetricsFormat::Csv => format!(
+        "{},{},{},{},{},{},{},{},{}",
+        status.iteration,
+        status.evaluations,
+        status.elapsed_seconds,
+        status.best_score,
+        status.current_score,
+        polygons,
+        vertices,
+        status.temperature.map(|t| t.to_string()).unwrap_or_default(),
+        mutation.unwrap_or_default()
+      ),
+      MetricsFormat::JsonLines => format!(
+        "{{\"iteration\": {}, \"evaluations\": {}, \"elapsed_seconds\": {}, \"best_score\": {}, \"current_score\": {}, \"polygons\": {}, \"vertices\": {}, \"temperature\": {}, \"accepted_mutation\": {}}}",
+        status.iteration,
+        status.evaluations,
+        status.elapsed_seconds,
+        status.best_score,
+        status.current_score,
+        polygons,
+        vertices,
+        status
+          .temperature
+          .map(|t| t.to_string())
+          .unwrap_or_else(|| "null".to_string()),
+        mutation
+          .map(|m| format!("\"{}\"", m))
+          .unwrap_or_else(|| "null".to_string())
+      ),
+    }
+  }
+
+  fn write(&mut self, status: &RunStatus) {
+    if self.failed {
+      return;
+    }
+    let line = self.record(status);
+    // Report the first failure only; the run carries on without logging
+    if let Err(err) = writeln!(self.out, "{}", line) {
+      eprintln!("Could not write metrics: {}", err);
+      self.failed = true;
+    }
+    self.last_logged = Some(status.iteration);
+  }
+}
+
+impl Observer for MetricsLog {
+  fn on_iteration(&mut self, status: &RunStatus) -> Control {
+    if status.iteration.is_multiple_of(self.interval) {
+      self.write(status);
+    }
+    Control::Continue
+  }
+
+  fn on_finish(&mut self, status: &RunStatus) {
+    if self.last_logged != Some(status.iteration) {
+      self.write(status);
+    }
+    if !self.failed {
+      if let Err(err) = self.out.flush() {
+        eprintln!("Could not write metrics: {}", err);
+      }
+    }
+  }
+}
@@ -1,47 +1,404 @@
-use rand::{prelude::*, rng};
+use rand::prelude::*;
 
-use crate::generations::{generate_random_point, generate_random_polygon};
+use crate::colour_solve::solve_polygon_colour;
+use crate::constraints::{insertion_index, PolygonConstraints, PolygonShape};
+use crate::generations::{generate_initial_image, generate_random_point, generate_random_polygon};
+use crate::palette::Palette;
+use crate::random::{rng, standard_cauchy, standard_normal};
 use crate::types::Image;
 
-const POINT_MOVE_DELTA: f32 = 5.0;
-const POLYGON_MOVE_DELTA: f32 = 3.0;
-const COLOUR_DELTA: i16 = 20;
-const MIN_POLYGONS: usize = 10;
-
-const MUTATION_TYPE_CHANCE: &[(&str, f32)] = &[
-  ("move-point", 0.30),
-  ("change-colour", 0.30),
-  ("move-polygon", 0.15),
-  ("reorder-polygon", 0.10),
-  ("add-polygon", 0.05),
-  ("remove-polygon", 0.05),
-  ("new-point", 0.03),
-  ("delete-point", 0.02),
-];
-
-pub fn add_point(mut image: Image) -> Image {
+const MIN_STEP_SIZE: f32 = 0.01;
+const MAX_STEP_SIZE: f32 = 100.0;
+// Cauchy draws are clamped to this many scale units to avoid infinities
+const CAUCHY_LIMIT: f32 = 50.0;
+// Success rate targeted by the 1/5th rule
+const TARGET_SUCCESS_RATE: f32 = 0.2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MutationType {
+  MovePoint,
+  ChangeColour,
+  MovePolygon,
+  ReorderPolygon,
+  AddPolygon,
+  RemovePolygon,
+  NewPoint,
+  DeletePoint,
+  /// Set a polygon's RGB to its least-squares optimum against the target
+  SolveColour,
+}
+
+impl MutationType {
+  pub const ALL: [MutationType; 9] = [
+    MutationType::MovePoint,
+    MutationType::ChangeColour,
+    MutationType::MovePolygon,
+    MutationType::ReorderPolygon,
+    MutationType::AddPolygon,
+    MutationType::RemovePolygon,
+    MutationType::NewPoint,
+    MutationType::DeletePoint,
+    MutationType::SolveColour,
+  ];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      MutationType::MovePoint => "move-point",
+      MutationType::ChangeColour => "change-colour",
+      MutationType::MovePolygon => "move-polygon",
+      MutationType::ReorderPolygon => "reorder-polygon",
+      MutationType::AddPolygon => "add-polygon",
+      MutationType::RemovePolygon => "remove-polygon",
+      MutationType::NewPoint => "new-point",
+      MutationType::DeletePoint => "delete-point",
+      MutationType::SolveColour => "solve-colour",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<MutationType> {
+    MutationType::ALL.into_iter().find(|t| t.name() == name)
+  }
+}
+
+/// Distribution the vertex, polygon and colour perturbations are drawn from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Perturbation {
+  /// Uniform in [-delta, delta]
+  Uniform,
+  /// Normal with standard deviation delta
+  Gaussian,
+  /// Cauchy with scale delta; mostly small steps with occasional long jumps
+  Cauchy,
+}
+
+impl Perturbation {
+  pub fn from_name(name: &str) -> Option<Perturbation> {
+    match name {
+      "uniform" => Some(Perturbation::Uniform),
+      "gaussian" => Some(Perturbation::Gaussian),
+      "cauchy" => Some(Perturbation::Cauchy),
+      _ => None,
+    }
+  }
+}
+
+/// Units of `point_move_delta` and `polygon_move_delta`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepScale {
+  /// Deltas are in pixels
+  Absolute,
+  /// Deltas are fractions of the image's longest side
+  Relative,
+}
+
+impl StepScale {
+  /// Default point and polygon deltas in this scale's units
+  pub fn default_deltas(self) -> (f32, f32) {
+    match self {
+      StepScale::Absolute => (5.0, 3.0),
+      StepScale::Relative => (0.02, 0.01),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepAdaptation {
+  /// Deltas stay as configured
+  Fixed,
+  /// 1/5th success rule: every `window` steps, grow `step_size` by `factor`
+  /// if more than a fifth of them improved the best score, shrink it otherwise
+  OneFifth { window: usize, factor: f32 },
+  /// Each individual carries its own `step_size`, perturbed log-normally
+  /// before mutating and inherited by surviving children
+  SelfAdaptive { learning_rate: f32 },
+}
+
+impl StepAdaptation {
+  /// Parse an adaptation name using the default parameters for that rule
+  pub fn from_name(name: &str) -> Option<StepAdaptation> {
+    match name {
+      "fixed" => Some(StepAdaptation::Fixed),
+      "one-fifth" => Some(StepAdaptation::OneFifth {
+        window: 50,
+        factor: 1.2,
+      }),
+      "self-adaptive" => Some(StepAdaptation::SelfAdaptive { learning_rate: 0.2 }),
+      _ => None,
+    }
+  }
+}
+
+/// Runtime mutation settings: operator weights and perturbation sizes
+#[derive(Clone, Debug)]
+pub struct MutationConfig {
+  /// Relative weight of each operator; weights don't need to sum to 1
+  pub weights: Vec<(MutationType, f32)>,
+  pub point_move_delta: f32,
+  pub polygon_move_delta: f32,
+  pub colour_delta: i16,
+  pub min_polygons: usize,
+  pub perturbation: Perturbation,
+  pub step_scale: StepScale,
+  pub adaptation: StepAdaptation,
+  /// Global multiplier on all deltas, adjusted by the 1/5th rule
+  pub step_size: f32,
+  /// Polygon validity every operator keeps
+  pub constraints: PolygonConstraints,
+  /// Colours polygons must use; colour mutations then switch between
+  /// entries instead of drifting RGB
+  pub palette: Option<Palette>,
+}
+
+impl Default for MutationConfig {
+  fn default() -> Self {
+    Self {
+      weights: vec![
+        (MutationType::MovePoint, 0.30),
+        (MutationType::ChangeColour, 0.30),
+        (MutationType::MovePolygon, 0.15),
+        (MutationType::ReorderPolygon, 0.10),
+        (MutationType::AddPolygon, 0.05),
+        (MutationType::RemovePolygon, 0.05),
+        (MutationType::NewPoint, 0.03),
+        (MutationType::DeletePoint, 0.02),
+      ],
+      point_move_delta: StepScale::Absolute.default_deltas().0,
+      polygon_move_delta: StepScale::Absolute.default_deltas().1,
+      colour_delta: 20,
+      min_polygons: 10,
+      perturbation: Perturbation::Uniform,
+      step_scale: StepScale::Absolute,
+      adaptation: StepAdaptation::Fixed,
+      step_size: 1.0,
+      constraints: PolygonConstraints::default(),
+      palette: None,
+    }
+  }
+}
+
+impl MutationConfig {
+  /// Weight of a single operator, 0 if missing
+  pub fn weight(&self, mutation_type: MutationType) -> f32 {
+    self
+      .weights
+      .iter()
+      .find(|(t, _)| *t == mutation_type)
+      .map_or(0.0, |(_, weight)| *weight)
+  }
+
+  /// Set the weight of a single operator, adding it if missing
+  pub fn set_weight(&mut self, mutation_type: MutationType, weight: f32) {
+    let weight = weight.max(0.0);
+    match self.weights.iter_mut().find(|(t, _)| *t == mutation_type) {
+      Some(entry) => entry.1 = weight,
+      None => self.weights.push((mutation_type, weight)),
+    }
+  }
+
+  /// Switch the units of the point and polygon deltas. Deltas still at the
+  /// old scale's defaults move to the new scale's, so the defaults never
+  /// become fractions of 3 to 5 times the image.
+  pub fn set_step_scale(&mut self, scale: StepScale) {
+    let (old_point, old_polygon) = self.step_scale.default_deltas();
+    let (point, polygon) = scale.default_deltas();
+    if self.point_move_delta == old_point {
+      self.point_move_delta = point;
+    }
+    if self.polygon_move_delta == old_polygon {
+      self.polygon_move_delta = polygon;
+    }
+    self.step_scale = scale;
+  }
+
+  /// Set the point, polygon and colour deltas, changing nothing if any is
+  /// negative or not finite
+  pub fn set_deltas(&mut self, point_move: f32, polygon_move: f32, colour: i16) -> Result<(), String> {
+    check_delta("point delta", point_move)?;
+    check_delta("polygon delta", polygon_move)?;
+    check_delta("colour delta", colour as f32)?;
+    self.point_move_delta = point_move;
+    self.polygon_move_delta = polygon_move;
+    self.colour_delta = colour;
+    Ok(())
+  }
+
+  /// Pick an operator at random according to the configured weights
+  pub fn choose(&self) -> MutationType {
+    let total: f32 = self.weights.iter().map(|(_, w)| w.max(0.0)).sum();
+    if total <= 0.0 {
+      return MutationType::MovePoint;
+    }
+
+    let mut rand_prob: f32 = rng().random_range(0.0..total);
+    for (mut_type, weight) in self.weights.iter() {
+      let weight = weight.max(0.0);
+      if rand_prob < weight {
+        return *mut_type;
+      }
+      rand_prob -= weight;
+    }
+
+    // Floating point leftovers land on the last weighted operator
+    self
+      .weights
+      .iter()
+      .rev()
+      .find(|(_, w)| *w > 0.0)
+      .map(|(t, _)| *t)
+      .unwrap_or(MutationType::MovePoint)
+  }
+
+  /// A random starting genome whose polygons meet the constraints and use
+  /// the palette
+  pub fn initial_image(&self, width: usize, height: usize) -> Image {
+    let mut image = self.constraints.repair(generate_initial_image(width, height)).0;
+    if let Some(palette) = &self.palette {
+      for polygon in &mut image.polygon {
+        let [r, g, b] = palette.random();
+        polygon.colour = [r, g, b, polygon.colour[3]];
+      }
+    }
+    image
+  }
+
+  /// Repair polygons that break the constraints and snap colours to the
+  /// palette, for genomes changed outside the mutation operators
+  pub fn conform(&self, image: Image) -> Image {
+    let image = self.constraints.repair(image).0;
+    match &self.palette {
+      Some(palette) => palette.snap(image).0,
+      None => image,
+    }
+  }
+
+  fn step_multiplier(&self, image: &Image) -> f32 {
+    self.step_size * image.step_size
+  }
+
+  /// Effective perturbation scale in pixels for a point or polygon delta
+  fn spatial_scale(&self, delta: f32, image: &Image) -> f32 {
+    let delta = match self.step_scale {
+      StepScale::Absolute => delta,
+      StepScale::Relative => delta * image.width.max(image.height) as f32,
+    };
+    delta.abs() * self.step_multiplier(image)
+  }
+
+  fn sample(&self, scale: f32) -> f32 {
+    if scale <= 0.0 {
+      return 0.0;
+    }
+    match self.perturbation {
+      Perturbation::Uniform => rng().random_range(-scale..=scale),
+      Perturbation::Gaussian => scale * standard_normal(),
+      Perturbation::Cauchy => scale * standard_cauchy().clamp(-CAUCHY_LIMIT, CAUCHY_LIMIT),
+    }
+  }
+}
+
+/// `delta` if it is a finite, non-negative perturbation size
+pub fn check_delta(name: &str, delta: f32) -> Result<f32, String> {
+  if delta.is_finite() && delta >= 0.0 {
+    Ok(delta)
+  } else {
+    Err(format!("{} must be a finite number of at least 0, got {}", name, delta))
+  }
+}
+
+/// Applies the configured step-size adaptation from observed step outcomes
+#[derive(Clone, Debug, Default)]
+pub struct StepSizeController {
+  trials: usize,
+  successes: usize,
+}
+
+impl StepSizeController {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Record whether a step improved the best score; under the 1/5th rule
+  /// this updates `config.step_size` once per window
+  pub fn record(&mut self, improved: bool, config: &mut MutationConfig) {
+    let StepAdaptation::OneFifth { window, factor } = config.adaptation else {
+      return;
+    };
+
+    self.trials += 1;
+    if improved {
+      self.successes += 1;
+    }
+
+    if self.trials >= window.max(1) {
+      let success_rate = self.successes as f32 / self.trials as f32;
+      if success_rate > TARGET_SUCCESS_RATE {
+        config.step_size *= factor;
+      } else if success_rate < TARGET_SUCCESS_RATE {
+        config.step_size /= factor;
+      }
+      config.step_size = config.step_size.clamp(MIN_STEP_SIZE, MAX_STEP_SIZE);
+      self.trials = 0;
+      self.successes = 0;
+    }
+  }
+}
+
+/// Perturb an individual's own step size when self-adaptation is enabled.
+/// Call once per child, before its mutations.
+pub fn adapt_step_size(mut image: Image, config: &MutationConfig) -> Image {
+  if let StepAdaptation::SelfAdaptive { learning_rate } = config.adaptation {
+    let factor = (learning_rate * standard_normal()).exp();
+    image.step_size = (image.step_size * factor).clamp(MIN_STEP_SIZE, MAX_STEP_SIZE);
+  }
+  image
+}
+
+/// Apply `change` to the points of polygon `idx`, undoing it if the
+/// result breaks the configured constraints
+fn change_points(image: &mut Image, idx: usize, config: &MutationConfig, change: impl FnOnce(&mut Vec<(f32, f32)>)) {
+  let points = &mut image.polygon[idx].points;
+  if !config.constraints.is_active() {
+    change(points);
+    return;
+  }
+  let original = points.clone();
+  change(points);
+  if !config.constraints.allows(points) {
+    *points = original;
+  }
+}
+
+pub fn add_point(mut image: Image, config: &MutationConfig) -> Image {
   if image.polygon.is_empty() {
     return image;
   }
   let mutate_poly = rng().random_range(0..image.polygon.len());
   let point = generate_random_point(image.width, image.height);
-  image.polygon[mutate_poly].points.push(point);
+  change_points(&mut image, mutate_poly, config, |points| {
+    match config.constraints.shape {
+      PolygonShape::Any => points.push(point),
+      // Appending almost always crosses an edge; splice in where it fits best
+      _ => points.insert(insertion_index(points, point), point),
+    }
+  });
   image
 }
 
-pub fn delete_point(mut image: Image) -> Image {
+pub fn delete_point(mut image: Image, config: &MutationConfig) -> Image {
   if image.polygon.is_empty() {
     return image;
   }
   let mutate_poly = rng().random_range(0..image.polygon.len());
   if image.polygon[mutate_poly].points.len() > 3 {
     let point = rng().random_range(0..image.polygon[mutate_poly].points.len());
-    image.polygon[mutate_poly].points.remove(point);
+    change_points(&mut image, mutate_poly, config, |points| {
+      points.remove(point);
+    });
   }
   image
 }
 
-pub fn move_point(mut image: Image) -> Image {
+pub fn move_point(mut image: Image, config: &MutationConfig) -> Image {
   if image.polygon.is_empty() {
     return image;
   }
@@ -50,29 +407,38 @@ pub fn move_point(mut image: Image) -> Image {
     return image;
   }
   let point_idx = rng().random_range(0..image.polygon[mutate_poly].points.len());
-  let point = &mut image.polygon[mutate_poly].points[point_idx];
 
-  let dx: f32 = rng().random_range(-POINT_MOVE_DELTA..=POINT_MOVE_DELTA);
-  let dy: f32 = rng().random_range(-POINT_MOVE_DELTA..=POINT_MOVE_DELTA);
+  let scale = config.spatial_scale(config.point_move_delta, &image);
+  let dx = config.sample(scale);
+  let dy = config.sample(scale);
 
-  point.0 = (point.0 + dx).clamp(0.0, image.width as f32 - 1.0);
-  point.1 = (point.1 + dy).clamp(0.0, image.height as f32 - 1.0);
+  let (width, height) = (image.width as f32, image.height as f32);
+  change_points(&mut image, mutate_poly, config, |points| {
+    let point = &mut points[point_idx];
+    point.0 = (point.0 + dx).clamp(0.0, width - 1.0);
+    point.1 = (point.1 + dy).clamp(0.0, height - 1.0);
+  });
   image
 }
 
-pub fn move_polygon(mut image: Image) -> Image {
+pub fn move_polygon(mut image: Image, config: &MutationConfig) -> Image {
   if image.polygon.is_empty() {
     return image;
   }
   let mutate_poly = rng().random_range(0..image.polygon.len());
 
-  let dx: f32 = rng().random_range(-POLYGON_MOVE_DELTA..=POLYGON_MOVE_DELTA);
-  let dy: f32 = rng().random_range(-POLYGON_MOVE_DELTA..=POLYGON_MOVE_DELTA);
+  let scale = config.spatial_scale(config.polygon_move_delta, &image);
+  let dx = config.sample(scale);
+  let dy = config.sample(scale);
 
-  for point in &mut image.polygon[mutate_poly].points {
-    point.0 = (point.0 + dx).clamp(0.0, image.width as f32 - 1.0);
-    point.1 = (point.1 + dy).clamp(0.0, image.height as f32 - 1.0);
-  }
+  // Clamping at the canvas edge can flatten or fold the polygon
+  let (width, height) = (image.width as f32, image.height as f32);
+  change_points(&mut image, mutate_poly, config, |points| {
+    for point in points {
+      point.0 = (point.0 + dx).clamp(0.0, width - 1.0);
+      point.1 = (point.1 + dy).clamp(0.0, height - 1.0);
+    }
+  });
   image
 }
 
@@ -86,56 +452,182 @@ pub fn reorder_polygon(mut image: Image) -> Image {
   image
 }
 
-pub fn change_colour(mut image: Image) -> Image {
+pub fn change_colour(mut image: Image, config: &MutationConfig) -> Image {
   if image.polygon.is_empty() {
     return image;
   }
   let mutate_poly = rng().random_range(0..image.polygon.len());
-  let colour = &mut image.polygon[mutate_poly].colour;
 
   let channel: usize = rng().random_range(0..4);
-  let delta: i16 = rng().random_range(-COLOUR_DELTA..=COLOUR_DELTA);
+  if let Some(palette) = config.palette.as_ref().filter(|_| channel < 3) {
+    let colour = &mut image.polygon[mutate_poly].colour;
+    let [r, g, b] = palette.switch([colour[0], colour[1], colour[2]]);
+    *colour = [r, g, b, colour[3]];
+    return image;
+  }
+  let scale = config.colour_delta.unsigned_abs() as f32 * config.step_multiplier(&image);
+  let delta = config.sample(scale).round().clamp(-255.0, 255.0) as i16;
 
+  let colour = &mut image.polygon[mutate_poly].colour;
   colour[channel] = (colour[channel] as i16 + delta).clamp(0, 255) as u8;
   image
 }
 
-pub fn add_polygon(mut image: Image) -> Image {
-  let new_poly = generate_random_polygon(image.width, image.height);
+pub fn add_polygon(mut image: Image, config: &MutationConfig) -> Image {
+  let mut new_poly = generate_random_polygon(image.width, image.height);
+  config.constraints.repair_points(&mut new_poly.points);
+  if let Some(palette) = &config.palette {
+    let [r, g, b] = palette.random();
+    new_poly.colour = [r, g, b, new_poly.colour[3]];
+  }
   image.polygon.push(new_poly);
   image
 }
 
-pub fn remove_polygon(mut image: Image) -> Image {
-  if image.polygon.len() > MIN_POLYGONS {
+pub fn remove_polygon(mut image: Image, config: &MutationConfig) -> Image {
+  if image.polygon.len() > config.min_polygons {
     let idx = rng().random_range(0..image.polygon.len());
     image.polygon.remove(idx);
   }
   image
 }
 
-pub fn mutate_image(image: Image) -> Image {
-  let mut rand_prob: f32 = rng().random_range(0.0..1.0);
-  let mut mutation_type: &str = "move-point"; // default
-
-  for (mut_type, prob) in MUTATION_TYPE_CHANCE.iter() {
-    if rand_prob <= *prob {
-      mutation_type = mut_type;
-      break;
-    } else {
-      rand_prob -= *prob;
+pub fn solve_colour(image: Image, config: &MutationConfig, target: Option<&[u8]>) -> Image {
+  match target {
+    Some(target) if !image.polygon.is_empty() => {
+      let idx = rng().random_range(0..image.polygon.len());
+      let mut image = solve_polygon_colour(image, idx, target);
+      // The least-squares colour is rarely an entry; take the closest one
+      if let Some(palette) = &config.palette {
+        let [r, g, b, a] = image.polygon[idx].colour;
+        let [r, g, b] = palette.nearest([r, g, b]);
+        image.polygon[idx].colour = [r, g, b, a];
+      }
+      image
     }
+    _ => image,
   }
+}
 
+/// Apply one operator. `target` holds the RGBA pixels for operators that
+/// fit the image (solve-colour); without it they change nothing.
+pub fn apply_mutation(
+  image: Image,
+  mutation_type: MutationType,
+  config: &MutationConfig,
+  target: Option<&[u8]>,
+) -> Image {
   match mutation_type {
-    "move-point" => move_point(image),
-    "change-colour" => change_colour(image),
-    "move-polygon" => move_polygon(image),
-    "reorder-polygon" => reorder_polygon(image),
-    "add-polygon" => add_polygon(image),
-    "remove-polygon" => remove_polygon(image),
-    "new-point" => add_point(image),
-    "delete-point" => delete_point(image),
-    _ => move_point(image),
+    MutationType::MovePoint => move_point(image, config),
+    MutationType::ChangeColour => change_colour(image, config),
+    MutationType::MovePolygon => move_polygon(image, config),
+    MutationType::ReorderPolygon => reorder_polygon(image),
+    MutationType::AddPolygon => add_polygon(image, config),
+    MutationType::RemovePolygon => remove_polygon(image, config),
+    MutationType::NewPoint => add_point(image, config),
+    MutationType::DeletePoint => delete_point(image, config),
+    MutationType::SolveColour => solve_colour(image, config, target),
+  }
+}
+
+/// Apply an operator drawn by weight, returning the mutated genome and the
+/// operator applied
+pub fn mutate_image(
+  image: Image,
+  config: &MutationConfig,
+  target: Option<&[u8]>,
+) -> (Image, MutationType) {
+  let mutation_type = config.choose();
+  (apply_mutation(image, mutation_type, config, target), mutation_type)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::random;
+
+  fn one_fifth() -> MutationConfig {
+    MutationConfig {
+      adaptation: StepAdaptation::OneFifth { window: 10, factor: 1.5 },
+      ..MutationConfig::default()
+    }
+  }
+
+  #[test]
+  fn one_fifth_rule_grows_on_success_and_shrinks_on_failure() {
+    let mut config = one_fifth();
+    let mut control = StepSizeController::new();
+
+    // Half of each window succeeds
+    for step in 0..10 {
+      control.record(step % 2 == 0, &mut config);
+    }
+    assert_eq!(config.step_size, 1.5);
+
+    // One success in ten is under a fifth
+    for step in 0..20 {
+      control.record(step % 10 == 0, &mut config);
+    }
+    assert!((config.step_size - 1.5 / 1.5 / 1.5).abs() < 1e-6);
+
+    // Exactly a fifth holds it
+    let before = config.step_size;
+    for step in 0..10 {
+      control.record(step % 5 == 0, &mut config);
+    }
+    assert_eq!(config.step_size, before);
+  }
+
+  #[test]
+  fn one_fifth_rule_stays_within_the_clamp_range() {
+    let mut config = one_fifth();
+    let mut control = StepSizeController::new();
+    for _ in 0..1000 {
+      control.record(true, &mut config);
+    }
+    assert_eq!(config.step_size, MAX_STEP_SIZE);
+    for _ in 0..2000 {
+      control.record(false, &mut config);
+    }
+    assert_eq!(config.step_size, MIN_STEP_SIZE);
+  }
+
+  #[test]
+  fn fixed_adaptation_leaves_step_sizes_alone() {
+    let mut config = MutationConfig::default();
+    let mut control = StepSizeController::new();
+    for _ in 0..100 {
+      control.record(true, &mut config);
+    }
+    assert_eq!(config.step_size, 1.0);
+
+    let image = adapt_step_size(config.initial_image(20, 20), &config);
+    assert_eq!(image.step_size, 1.0);
+  }
+
+  #[test]
+  fn self_adaptation_follows_selection_within_the_clamp_range() {
+    random::seed(11);
+    let config = MutationConfig {
+      adaptation: StepAdaptation::SelfAdaptive { learning_rate: 0.2 },
+      ..MutationConfig::default()
+    };
+    let parent = config.initial_image(20, 20);
+
+    // Selection keeping the child with the larger step size, as when large
+    // steps succeed, drives it up; keeping the smaller drives it down
+    let select = |keep_larger: bool| {
+      let mut image = parent.clone();
+      for _ in 0..500 {
+        let a = adapt_step_size(image.clone(), &config);
+        let b = adapt_step_size(image, &config);
+        assert!((MIN_STEP_SIZE..=MAX_STEP_SIZE).contains(&a.step_size));
+        image = if (a.step_size > b.step_size) == keep_larger { a } else { b };
+      }
+      image.step_size
+    };
+    // Ends near a bound, where a child can still step back from it
+    assert!(select(true) > MAX_STEP_SIZE / 10.0);
+    assert!(select(false) < MIN_STEP_SIZE * 10.0);
   }
 }
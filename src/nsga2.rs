@@ -0,0 +1,381 @@
+use rand::prelude::*;
+
+use crate::crossover::{crossover, CrossoverType};
+use crate::mutations::{adapt_step_size, mutate_image, MutationConfig, MutationType};
+use crate::random::rng;
+use crate::types::Image;
+
+/// Genome size objective minimised alongside maximising accuracy
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Complexity {
+  /// Total vertices over all polygons
+  Vertices,
+  Polygons,
+}
+
+impl Complexity {
+  pub fn name(self) -> &'static str {
+    match self {
+      Complexity::Vertices => "vertices",
+      Complexity::Polygons => "polygons",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Complexity> {
+    match name {
+      "vertices" => Some(Complexity::Vertices),
+      "polygons" => Some(Complexity::Polygons),
+      _ => None,
+    }
+  }
+
+  pub fn measure(self, image: &Image) -> usize {
+    match self {
+      Complexity::Vertices => image.polygon.iter().map(|p| p.points.len()).sum(),
+      Complexity::Polygons => image.polygon.len(),
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct Nsga2Config {
+  pub population_size: usize,
+  pub complexity: Complexity,
+  pub crossover_type: CrossoverType,
+  pub crossover_rate: f32,
+  pub mutation_rate: f32,
+}
+
+impl Default for Nsga2Config {
+  fn default() -> Self {
+    Self {
+      population_size: 40,
+      complexity: Complexity::Vertices,
+      crossover_type: CrossoverType::Uniform,
+      crossover_rate: 0.7,
+      mutation_rate: 0.9,
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct Individual {
+  pub image: Image,
+  pub accuracy: f32,
+  pub complexity: usize,
+  /// Index of the non-dominated front, 0 being the Pareto front
+  pub rank: usize,
+  crowding: f32,
+}
+
+impl Individual {
+  fn new(image: Image, accuracy: f32, complexity: Complexity) -> Self {
+    Self {
+      complexity: complexity.measure(&image),
+      image,
+      accuracy,
+      rank: 0,
+      crowding: 0.0,
+    }
+  }
+
+  /// At least as accurate and as small, and strictly better in one
+  fn dominates(&self, other: &Individual) -> bool {
+    self.accuracy >= other.accuracy
+      && self.complexity <= other.complexity
+      && (self.accuracy > other.accuracy || self.complexity < other.complexity)
+  }
+}
+
+/// NSGA-II (Deb et al. 2002) trading accuracy against genome complexity.
+/// The population is kept in crowded-comparison order: by front, then by
+/// crowding distance, so a lower index is always the better individual.
+pub struct Nsga2 {
+  config: Nsga2Config,
+  population: Vec<Individual>,
+}
+
+impl Nsga2 {
+  pub fn new<F: FnMut(&Image) -> f32>(
+    width: usize,
+    height: usize,
+    config: &Nsga2Config,
+    mutation: &MutationConfig,
+    mut evaluate: F,
+  ) -> Self {
+    let population = (0..config.population_size.max(2))
+      .map(|_| {
+        let image = mutation.initial_image(width, height);
+        let accuracy = evaluate(&image);
+        Individual::new(image, accuracy, config.complexity)
+      })
+      .collect();
+
+    let mut nsga2 = Self {
+      config: config.clone(),
+      population,
+    };
+    let size = nsga2.population.len();
+    nsga2.select(size);
+    nsga2
+  }
+
+  pub fn population(&self) -> &[Individual] {
+    &self.population
+  }
+
+  /// Non-dominated individuals, smallest first
+  pub fn front(&self) -> Vec<&Individual> {
+    let mut front: Vec<&Individual> = self.population.iter().filter(|i| i.rank == 0).collect();
+    front.sort_by(|a, b| {
+      a.complexity
+        .cmp(&b.complexity)
+        .then(b.accuracy.partial_cmp(&a.accuracy).unwrap())
+    });
+    front.dedup_by(|a, b| a.complexity == b.complexity && a.accuracy == b.accuracy);
+    front
+  }
+
+  /// Most accurate individual, whatever its size
+  pub fn best(&self) -> (f32, &Image) {
+    let best = self
+      .population
+      .iter()
+      .max_by(|a, b| a.accuracy.partial_cmp(&b.accuracy).unwrap())
+      .unwrap();
+    (best.accuracy, &best.image)
+  }
+
+  /// One generation: breed a full set of offspring and keep the best half
+  /// of parents plus offspring by rank and crowding distance. `target` is
+  /// passed to the mutation operators. Returns the operator behind the most
+  /// accurate mutated offspring, if any.
+  pub fn step<F: FnMut(&Image) -> f32>(
+    &mut self,
+    mutation: &MutationConfig,
+    target: &[u8],
+    mut evaluate: F,
+  ) -> Option<MutationType> {
+    let size = self.population.len();
+    let mut rng = rng();
+    let mut best_offspring: Option<(f32, Option<MutationType>)> = None;
+
+    for _ in 0..size {
+      let first = &self.population[self.binary_tournament()].image;
+      let mut child = if rng.random::<f32>() < self.config.crossover_rate {
+        let second = &self.population[self.binary_tournament()].image;
+        crossover(first, second, self.config.crossover_type, mutation.min_polygons)
+      } else {
+        first.clone()
+      };
+
+      let mut operator = None;
+      if rng.random::<f32>() < self.config.mutation_rate {
+        let adapted = adapt_step_size(child, mutation);
+        let (mutated, applied) = mutate_image(adapted, mutation, Some(target));
+        child = mutated;
+        operator = Some(applied);
+      }
+
+      let accuracy = evaluate(&child);
+      if best_offspring.is_none_or(|(best, _)| accuracy > best) {
+        best_offspring = Some((accuracy, operator));
+      }
+      self
+        .population
+        .push(Individual::new(child, accuracy, self.config.complexity));
+    }
+
+    self.select(size);
+    best_offspring.and_then(|(_, operator)| operator)
+  }
+
+  /// Add an outside genome, e.g. a migrant; it survives only if it ranks
+  /// within the population
+  pub fn insert(&mut self, image: Image, accuracy: f32) {
+    let size = self.population.len();
+    self
+      .population
+      .push(Individual::new(image, accuracy, self.config.complexity));
+    self.select(size);
+  }
+
+  /// Re-score every individual off the Pareto front after `shake` has
+  /// modified it, then re-rank
+  pub fn perturb<F: FnMut(&mut Image) -> f32>(&mut self, mut shake: F) {
+    for individual in self.population.iter_mut().filter(|i| i.rank > 0) {
+      individual.accuracy = shake(&mut individual.image);
+      individual.complexity = self.config.complexity.measure(&individual.image);
+    }
+    let size = self.population.len();
+    self.select(size);
+  }
+
+  fn binary_tournament(&self) -> usize {
+    let mut rng = rng();
+    let a = rng.random_range(0..self.population.len());
+    let b = rng.random_range(0..self.population.len());
+    a.min(b)
+  }
+
+  /// Rank the population into fronts, assign crowding distances and keep
+  /// the best `size` individuals in crowded-comparison order
+  fn select(&mut self, size: usize) {
+    let mut remaining = std::mem::take(&mut self.population);
+    let mut rank = 0;
+
+    while !remaining.is_empty() && self.population.len() < size {
+      // Nobody dominates themselves, so no need to skip the candidate
+      let dominated: Vec<bool> = remaining
+        .iter()
+        .map(|candidate| remaining.iter().any(|other| other.dominates(candidate)))
+        .collect();
+      let (mut front, mut rest) = (Vec::new(), Vec::new());
+      for (individual, dominated) in remaining.into_iter().zip(dominated) {
+        if dominated {
+          rest.push(individual);
+        } else {
+          front.push(individual);
+        }
+      }
+      remaining = rest;
+
+      for individual in front.iter_mut() {
+        individual.rank = rank;
+      }
+      assign_crowding(&mut front);
+      front.sort_by(|a, b| b.crowding.partial_cmp(&a.crowding).unwrap());
+
+      let room = size - self.population.len();
+      self.population.extend(front.into_iter().take(room));
+      rank += 1;
+    }
+  }
+}
+
+/// Crowding distance over both objectives; boundary points are infinite so
+/// the extremes of every front are always kept
+fn assign_crowding(front: &mut [Individual]) {
+  let n = front.len();
+  for individual in front.iter_mut() {
+    individual.crowding = 0.0;
+  }
+  if n < 3 {
+    for individual in front.iter_mut() {
+      individual.crowding = f32::INFINITY;
+    }
+    return;
+  }
+
+  let objectives: [fn(&Individual) -> f32; 2] = [|i| i.accuracy, |i| i.complexity as f32];
+  for objective in objectives {
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|a, b| objective(&front[*a]).partial_cmp(&objective(&front[*b])).unwrap());
+
+    let low = objective(&front[order[0]]);
+    let high = objective(&front[order[n - 1]]);
+    front[order[0]].crowding = f32::INFINITY;
+    front[order[n - 1]].crowding = f32::INFINITY;
+    if high <= low {
+      continue;
+    }
+
+    for k in 1..n - 1 {
+      let gap = objective(&front[order[k + 1]]) - objective(&front[order[k - 1]]);
+      front[order[k]].crowding += gap / (high - low);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::Polygon;
+
+  fn individual(accuracy: f32, polygons: usize) -> Individual {
+    let image = Image {
+      polygon: vec![
+        Polygon {
+          points: vec![(0.0, 0.0), (4.0, 0.0), (0.0, 4.0)],
+          colour: [0, 0, 0, 255],
+        };
+        polygons
+      ],
+      width: 8,
+      height: 8,
+      step_size: 1.0,
+    };
+    Individual::new(image, accuracy, Complexity::Polygons)
+  }
+
+  /// Three fronts, with a duplicate on the first
+  fn population() -> Nsga2 {
+    Nsga2 {
+      config: Nsga2Config {
+        complexity: Complexity::Polygons,
+        ..Nsga2Config::default()
+      },
+      population: vec![
+        individual(0.5, 3),
+        individual(0.7, 10),
+        individual(0.9, 10),
+        individual(0.8, 6),
+        individual(0.75, 6),
+        individual(0.6, 1),
+        individual(0.85, 10),
+        individual(0.7, 3),
+        individual(0.8, 6),
+      ],
+    }
+  }
+
+  fn find(nsga2: &Nsga2, accuracy: f32, complexity: usize) -> &Individual {
+    nsga2
+      .population
+      .iter()
+      .find(|i| i.accuracy == accuracy && i.complexity == complexity)
+      .unwrap()
+  }
+
+  #[test]
+  fn ranks_fronts_with_infinite_crowding_at_the_extremes() {
+    let mut nsga2 = population();
+    nsga2.select(9);
+
+    let ranks: Vec<usize> = nsga2.population.iter().map(|i| i.rank).collect();
+    assert_eq!(ranks, vec![0, 0, 0, 0, 0, 1, 1, 1, 2]);
+    let expected = [(0.9, 10, 0), (0.6, 1, 0), (0.85, 10, 1), (0.5, 3, 1), (0.7, 10, 2)];
+    for (accuracy, complexity, rank) in expected {
+      assert_eq!(find(&nsga2, accuracy, complexity).rank, rank);
+    }
+
+    assert_eq!(find(&nsga2, 0.9, 10).crowding, f32::INFINITY);
+    assert_eq!(find(&nsga2, 0.6, 1).crowding, f32::INFINITY);
+    assert!(find(&nsga2, 0.7, 3).crowding.is_finite());
+    assert!(find(&nsga2, 0.75, 6).crowding.is_finite());
+    // Crowded-comparison order: extremes lead their front
+    assert_eq!(nsga2.population[0].crowding, f32::INFINITY);
+    assert_eq!(nsga2.population[1].crowding, f32::INFINITY);
+  }
+
+  #[test]
+  fn truncation_keeps_the_extremes_of_the_last_front() {
+    let mut nsga2 = population();
+    nsga2.select(6);
+
+    let last = &nsga2.population[5];
+    assert_eq!(last.rank, 1);
+    assert_eq!(last.crowding, f32::INFINITY);
+    assert!([(0.85, 10), (0.5, 3)].contains(&(last.accuracy, last.complexity)));
+  }
+
+  #[test]
+  fn front_is_deduplicated_and_smallest_first() {
+    let mut nsga2 = population();
+    nsga2.select(9);
+
+    let front: Vec<(f32, usize)> =
+      nsga2.front().iter().map(|i| (i.accuracy, i.complexity)).collect();
+    assert_eq!(front, vec![(0.6, 1), (0.7, 3), (0.8, 6), (0.9, 10)]);
+  }
+}
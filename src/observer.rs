@@ -0,0 +1,138 @@
+use crate::mutations::MutationType;
+use crate::types::Image;
+
+/// What an observer sees of a run after each iteration
+pub struct RunStatus<'a> {
+  pub iteration: usize,
+  pub evaluations: usize,
+  pub elapsed_seconds: f64,
+  /// Best score over the whole run, including genomes archived by restarts
+  pub best_score: f32,
+  /// Score of the search's current solution: the SA state, otherwise the
+  /// best member since the last restart
+  pub current_score: f32,
+  /// Simulated annealing temperature
+  pub temperature: Option<f32>,
+  /// Operator behind the solution accepted this iteration, if any
+  pub accepted_mutation: Option<MutationType>,
+  /// Iterations since the best score last improved
+  pub stagnation: usize,
+  pub best: &'a Image,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Control {
+  Continue,
+  /// Stop the run after this iteration; the best genome so far is returned
+  Cancel,
+}
+
+/// Receives progress from a running optimiser and can cancel it
+pub trait Observer {
+  fn on_iteration(&mut self, status: &RunStatus) -> Control;
+
+  /// Called once when the run ends, whether finished or cancelled
+  fn on_finish(&mut self, _status: &RunStatus) {}
+}
+
+/// No observer
+impl Observer for () {
+  fn on_iteration(&mut self, _status: &RunStatus) -> Control {
+    Control::Continue
+  }
+}
+
+/// Closures observe every iteration
+impl<F: FnMut(&RunStatus) -> Control> Observer for F {
+  fn on_iteration(&mut self, status: &RunStatus) -> Control {
+    self(status)
+  }
+}
+
+/// Several observers; the run is cancelled if any of them asks
+impl Observer for Vec<Box<dyn Observer + '_>> {
+  fn on_iteration(&mut self, status: &RunStatus) -> Control {
+    let mut control = Control::Continue;
+    for observer in self.iter_mut() {
+      if observer.on_iteration(status) == Control::Cancel {
+        control = Control::Cancel;
+      }
+    }
+    control
+  }
+
+  fn on_finish(&mut self, status: &RunStatus) {
+    for observer in self.iter_mut() {
+      observer.on_finish(status);
+    }
+  }
+}
+
+/// Prints progress to stdout every `interval` iterations and a summary at the end
+#[cfg(feature = "cli")]
+pub struct ConsoleProgress {
+  pub interval: usize,
+}
+
+#[cfg(feature = "cli")]
+impl Observer for ConsoleProgress {
+  fn on_iteration(&mut self, status: &RunStatus) -> Control {
+    if status.iteration.is_multiple_of(self.interval.max(1)) {
+      println!(
+        "Iteration {}: accuracy = {:.4}% (stale: {})",
+        status.iteration,
+        status.best_score * 100.0,
+        status.stagnation
+      );
+    }
+    Control::Continue
+  }
+
+  fn on_finish(&mut self, status: &RunStatus) {
+    println!(
+      "Finished after {} iterations with accuracy {:.4}%",
+      status.iteration,
+      status.best_score * 100.0
+    );
+  }
+}
+
+/// Saves the best genome as `<dir>/<iteration>.png` every `interval` iterations
+#[cfg(feature = "cli")]
+pub struct SnapshotSaver {
+  dir: std::path::PathBuf,
+  interval: usize,
+  render: fn(&Image) -> image::RgbaImage,
+}
+
+#[cfg(feature = "cli")]
+impl SnapshotSaver {
+  /// Create `dir` if needed; `render` should match the renderer the run scores with
+  pub fn new(
+    dir: impl Into<std::path::PathBuf>,
+    interval: usize,
+    render: fn(&Image) -> image::RgbaImage,
+  ) -> std::io::Result<Self> {
+    let dir = dir.into();
+    std::fs::create_dir_all(&dir)?;
+    Ok(Self {
+      dir,
+      interval: interval.max(1),
+      render,
+    })
+  }
+}
+
+#[cfg(feature = "cli")]
+impl Observer for SnapshotSaver {
+  fn on_iteration(&mut self, status: &RunStatus) -> Control {
+    if status.iteration.is_multiple_of(self.interval) {
+      // Zero-padded so the files sort in iteration order
+      let path = self.dir.join(format!("{:08}.png", status.iteration));
+      if let Err(err) = (self.render)(status.best).save(&path) {
+        eprintln!("Could not save snapshot {}: {}", path.display(), err);
+      }
+    }
+    Control::Continue
+  }
+}
@@ -0,0 +1,289 @@
+use std::cmp::Ordering;
+
+use rand::prelude::*;
+
+use crate::random::rng;
+use crate::types::Image;
+
+// Pixels sampled for k-means; larger targets are subsampled evenly
+const KMEANS_SAMPLES: usize = 20_000;
+const KMEANS_ITERATIONS: usize = 10;
+
+/// How a palette is extracted from the target
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quantizer {
+  /// Repeatedly split the colour box with the widest channel at its median
+  MedianCut,
+  /// Lloyd's k-means, started from the median-cut palette
+  KMeans,
+}
+
+impl Quantizer {
+  pub fn name(self) -> &'static str {
+    match self {
+      Quantizer::MedianCut => "median-cut",
+      Quantizer::KMeans => "kmeans",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Quantizer> {
+    match name {
+      "median-cut" => Some(Quantizer::MedianCut),
+      "kmeans" => Some(Quantizer::KMeans),
+      _ => None,
+    }
+  }
+}
+
+/// Fixed set of RGB colours polygons must use; alpha stays free
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+  pub colours: Vec<[u8; 3]>,
+}
+
+fn distance_squared(a: [u8; 3], b: [f32; 3]) -> f32 {
+  (0..3).map(|c| (a[c] as f32 - b[c]).powi(2)).sum()
+}
+
+fn parse_hex(text: &str) -> Option<[u8; 3]> {
+  let hex = text.strip_prefix('#').unwrap_or(text);
+  if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+    return None;
+  }
+  let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+  Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+impl Palette {
+  /// Parse a GIMP palette (.gpl) or a list of hex colours such as `#1a2b3c`,
+  /// separated by whitespace or commas. In hex lists anything from a `#`
+  /// that doesn't start a colour is a comment.
+  pub fn parse(text: &str) -> Result<Palette, String> {
+    let mut colours = Vec::new();
+    if text.trim_start().starts_with("GIMP Palette") {
+      for (number, line) in text.lines().enumerate().skip(1) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("Name:") || line.starts_with("Columns:") {
+          continue;
+        }
+        let channels: Vec<u8> = line
+          .split_whitespace()
+          .take(3)
+          .map(|value| value.parse())
+          .collect::<Result<_, _>>()
+          .map_err(|_| format!("Line {}: expected R G B", number + 1))?;
+        match channels.as_slice() {
+          [r, g, b] => colours.push([*r, *g, *b]),
+          _ => return Err(format!("Line {}: expected R G B", number + 1)),
+        }
+      }
+    } else {
+      for (number, line) in text.lines().enumerate() {
+        for word in line.split([' ', '\t', ',']).filter(|w| !w.is_empty()) {
+          match parse_hex(word) {
+            Some(colour) => colours.push(colour),
+            None if word.starts_with('#') => break,
+            None => return Err(format!("Line {}: invalid colour {}", number + 1, word)),
+          }
+        }
+      }
+    }
+
+    if colours.is_empty() {
+      return Err("Palette has no colours".to_string());
+    }
+    Ok(Palette {
+      colours: unique(colours),
+    })
+  }
+
+  /// Up to `size` colours summarising the opaque pixels of an RGBA target
+  pub fn from_target(pixels: &[u8], size: usize, quantizer: Quantizer) -> Palette {
+    let mut samples: Vec<[u8; 3]> = pixels
+      .chunks_exact(4)
+      .filter(|p| p[3] > 0)
+      .map(|p| [p[0], p[1], p[2]])
+      .collect();
+    if samples.is_empty() {
+      samples.push([0, 0, 0]);
+    }
+
+    let colours = median_cut(&mut samples.clone(), size.max(1));
+    let colours = match quantizer {
+      Quantizer::MedianCut => colours,
+      Quantizer::KMeans => {
+        let stride = samples.len().div_ceil(KMEANS_SAMPLES);
+        let sampled: Vec<[u8; 3]> = samples.iter().step_by(stride).copied().collect();
+        kmeans(&sampled, colours)
+      }
+    };
+    Palette {
+      colours: unique(colours),
+    }
+  }
+
+  pub fn nearest(&self, colour: [u8; 3]) -> [u8; 3] {
+    let colour = colour.map(|c| c as f32);
+    *self
+      .colours
+      .iter()
+      .min_by(|a, b| {
+        distance_squared(**a, colour)
+          .partial_cmp(&distance_squared(**b, colour))
+          .unwrap_or(Ordering::Equal)
+      })
+      .unwrap()
+  }
+
+  pub fn random(&self) -> [u8; 3] {
+    self.colours[rng().random_range(0..self.colours.len())]
+  }
+
+  /// A different entry from `current` when there is one
+  pub fn switch(&self, current: [u8; 3]) -> [u8; 3] {
+    if self.colours.len() < 2 {
+      return self.colours[0];
+    }
+    loop {
+      let colour = self.random();
+      if colour != current {
+        return colour;
+      }
+    }
+  }
+
+  /// Move every polygon's RGB to its nearest palette entry, returning the
+  /// image and the number of polygons changed
+  pub fn snap(&self, mut image: Image) -> (Image, usize) {
+    let mut snapped = 0;
+    for polygon in &mut image.polygon {
+      let [r, g, b, a] = polygon.colour;
+      let [nr, ng, nb] = self.nearest([r, g, b]);
+      if [nr, ng, nb] != [r, g, b] {
+        polygon.colour = [nr, ng, nb, a];
+        snapped += 1;
+      }
+    }
+    (image, snapped)
+  }
+
+  /// `#rrggbb` entries separated by spaces
+  pub fn to_hex(&self) -> String {
+    self
+      .colours
+      .iter()
+      .map(|[r, g, b]| format!("#{:02x}{:02x}{:02x}", r, g, b))
+      .collect::<Vec<_>>()
+      .join(" ")
+  }
+}
+
+/// `colours` without repeats, in first-seen order
+fn unique(colours: Vec<[u8; 3]>) -> Vec<[u8; 3]> {
+  let mut seen = Vec::with_capacity(colours.len());
+  for colour in colours {
+    if !seen.contains(&colour) {
+      seen.push(colour);
+    }
+  }
+  seen
+}
+
+fn mean(pixels: &[[u8; 3]]) -> [u8; 3] {
+  let n = pixels.len().max(1) as f32;
+  let sum = |c: usize| pixels.iter().map(|p| p[c] as f32).sum::<f32>();
+  [0, 1, 2].map(|c| (sum(c) / n).round() as u8)
+}
+
+/// Widest channel of `pixels` and its range
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+  (0..3)
+    .map(|c| {
+      let min = pixels.iter().map(|p| p[c]).min().unwrap_or(0);
+      let max = pixels.iter().map(|p| p[c]).max().unwrap_or(0);
+      (c, max - min)
+    })
+    .max_by_key(|&(_, range)| range)
+    .unwrap()
+}
+
+fn median_cut(pixels: &mut [[u8; 3]], size: usize) -> Vec<[u8; 3]> {
+  let mut boxes: Vec<&mut [[u8; 3]]> = vec![pixels];
+  while boxes.len() < size {
+    // Split the box spanning the widest range; stop once all are single colours
+    let Some((index, (channel, _))) = boxes
+      .iter()
+      .map(|b| widest_channel(b))
+      .enumerate()
+      .filter(|(_, (_, range))| *range > 0)
+      .max_by_key(|(_, (_, range))| *range)
+    else {
+      break;
+    };
+    let widest = boxes.swap_remove(index);
+    widest.sort_unstable_by_key(|p| p[channel]);
+    let (low, high) = widest.split_at_mut(widest.len() / 2);
+    boxes.push(low);
+    boxes.push(high);
+  }
+  boxes.iter().map(|b| mean(b)).collect()
+}
+
+fn kmeans(pixels: &[[u8; 3]], mut centres: Vec<[u8; 3]>) -> Vec<[u8; 3]> {
+  for _ in 0..KMEANS_ITERATIONS {
+    let mut sums = vec![[0.0f32; 3]; centres.len()];
+    let mut counts = vec![0usize; centres.len()];
+    for pixel in pixels {
+      let colour = pixel.map(|c| c as f32);
+      let closest = (0..centres.len())
+        .min_by(|&a, &b| {
+          distance_squared(centres[a], colour)
+            .partial_cmp(&distance_squared(centres[b], colour))
+            .unwrap_or(Ordering::Equal)
+        })
+        .unwrap();
+      for c in 0..3 {
+        sums[closest][c] += colour[c];
+      }
+      counts[closest] += 1;
+    }
+
+    let mut moved = false;
+    for (centre, (sum, count)) in centres.iter_mut().zip(sums.iter().zip(&counts)) {
+      // Empty clusters keep their centre
+      if *count > 0 {
+        let updated = sum.map(|s| (s / *count as f32).round() as u8);
+        moved |= updated != *centre;
+        *centre = updated;
+      }
+    }
+    if !moved {
+      break;
+    }
+  }
+  centres
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_hex_list() {
+    let palette = Palette::parse("#ff0000, 00ff00\n#0000ff # blue\n#ff0000").unwrap();
+    assert_eq!(palette.colours, vec![[255, 0, 0], [0, 255, 0], [0, 0, 255]]);
+  }
+
+  #[test]
+  fn parse_gimp_palette() {
+    let text = "GIMP Palette\nName: Test\nColumns: 2\n# comment\n255 128 0 Orange\n  0   0 0\n";
+    assert_eq!(Palette::parse(text).unwrap().colours, vec![[255, 128, 0], [0, 0, 0]]);
+  }
+
+  #[test]
+  fn parse_rejects_bad_palettes() {
+    assert_eq!(Palette::parse("").err().unwrap(), "Palette has no colours");
+    assert_eq!(Palette::parse("#ff0000 12345g").err().unwrap(), "Line 1: invalid colour 12345g");
+    assert!(Palette::parse("GIMP Palette\n255 128\n").is_err());
+  }
+}
@@ -0,0 +1,175 @@
+use rand::prelude::*;
+
+use crate::encoding::Topology;
+use crate::random::rng;
+use crate::types::Image;
+
+#[derive(Clone, Debug)]
+pub struct PsoConfig {
+  pub swarm_size: usize,
+  /// Weight of a particle's previous velocity
+  pub inertia: f32,
+  /// Pull towards the particle's own best position
+  pub cognitive: f32,
+  /// Pull towards the swarm's best position
+  pub social: f32,
+  /// Maximum speed per dimension, as a fraction of the canvas / colour range
+  pub max_velocity: f32,
+}
+
+impl Default for PsoConfig {
+  fn default() -> Self {
+    // Constriction-equivalent coefficients (Clerc & Kennedy 2002)
+    Self {
+      swarm_size: 20,
+      inertia: 0.729,
+      cognitive: 1.494,
+      social: 1.494,
+      max_velocity: 0.1,
+    }
+  }
+}
+
+struct Particle {
+  position: Vec<f32>,
+  velocity: Vec<f32>,
+  best_position: Vec<f32>,
+  best_score: f32,
+}
+
+/// Particle swarm over the flattened genome of a fixed-topology `Image`.
+/// Positions live in the normalised space of `encoding::Topology`, so the
+/// [0, 1] bounds of every dimension are the canvas and colour bounds.
+pub struct ParticleSwarm {
+  topology: Topology,
+  config: PsoConfig,
+  particles: Vec<Particle>,
+  best_position: Vec<f32>,
+  best: (f32, Image),
+}
+
+impl ParticleSwarm {
+  /// Seed the swarm with `initial` plus random images of the same topology
+  pub fn new<F: FnMut(&Image) -> f32>(initial: &Image, config: &PsoConfig, mut evaluate: F) -> Self {
+    let topology = Topology::of(initial);
+    let mut rng = rng();
+    let vmax = config.max_velocity.abs();
+
+    let particles: Vec<Particle> = (0..config.swarm_size.max(1))
+      .map(|i| {
+        let image = if i == 0 {
+          initial.clone()
+        } else {
+          topology.random_image()
+        };
+        let score = evaluate(&image);
+        let position = topology.encode(&image);
+        let velocity = position
+          .iter()
+          .map(|_| rng.random_range(-vmax..=vmax))
+          .collect();
+        Particle {
+          best_position: position.clone(),
+          position,
+          velocity,
+          best_score: score,
+        }
+      })
+      .collect();
+
+    let leader = particles
+      .iter()
+      .max_by(|a, b| a.best_score.partial_cmp(&b.best_score).unwrap())
+      .unwrap();
+    let best_position = leader.best_position.clone();
+    let best = (leader.best_score, topology.decode(&best_position));
+
+    Self {
+      topology,
+      config: config.clone(),
+      particles,
+      best_position,
+      best,
+    }
+  }
+
+  pub fn topology(&self) -> &Topology {
+    &self.topology
+  }
+
+  pub fn best(&self) -> (f32, &Image) {
+    (self.best.0, &self.best.1)
+  }
+
+  /// Replace the worst particle with a genome found elsewhere (e.g. a migrant).
+  /// Ignored unless it has this topology.
+  pub fn replace_worst(&mut self, image: &Image, score: f32) {
+    if !self.topology.matches(image) {
+      return;
+    }
+    let Some(worst) = self
+      .particles
+      .iter_mut()
+      .min_by(|a, b| a.best_score.partial_cmp(&b.best_score).unwrap())
+    else {
+      return;
+    };
+
+    worst.position = self.topology.encode(image);
+    worst.velocity.iter_mut().for_each(|v| *v = 0.0);
+    worst.best_position.clone_from(&worst.position);
+    worst.best_score = score;
+
+    if score > self.best.0 {
+      self.best_position.clone_from(&worst.position);
+      self.best = (score, image.clone());
+    }
+  }
+
+  /// Move every particle once and score its new position
+  pub fn step<F: FnMut(&Image) -> f32>(&mut self, mut evaluate: F) {
+    let mut rng = rng();
+    let PsoConfig {
+      inertia,
+      cognitive,
+      social,
+      max_velocity,
+      ..
+    } = self.config;
+    let vmax = max_velocity.abs();
+
+    for particle in self.particles.iter_mut() {
+      for d in 0..particle.position.len() {
+        let r1: f32 = rng.random();
+        let r2: f32 = rng.random();
+        let x = particle.position[d];
+
+        let v = inertia * particle.velocity[d]
+          + cognitive * r1 * (particle.best_position[d] - x)
+          + social * r2 * (self.best_position[d] - x);
+        let v = v.clamp(-vmax, vmax);
+
+        // Stop at the canvas / colour bounds instead of flying past them
+        let next = x + v;
+        if (0.0..=1.0).contains(&next) {
+          particle.position[d] = next;
+          particle.velocity[d] = v;
+        } else {
+          particle.position[d] = next.clamp(0.0, 1.0);
+          particle.velocity[d] = 0.0;
+        }
+      }
+
+      let image = self.topology.decode(&particle.position);
+      let score = evaluate(&image);
+      if score > particle.best_score {
+        particle.best_score = score;
+        particle.best_position.clone_from(&particle.position);
+      }
+      if score > self.best.0 {
+        self.best_position.clone_from(&particle.position);
+        self.best = (score, image);
+      }
+    }
+  }
+}
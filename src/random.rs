@@ -0,0 +1,60 @@
+use std::cell::RefCell;
+use std::f32::consts::PI;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+thread_local! {
+  static THREAD_RNG: RefCell<StdRng> = RefCell::new(StdRng::from_os_rng());
+}
+
+/// Handle to this thread's generator, which every search operator draws from.
+/// Entropy-seeded by default; call `seed` first for a reproducible run.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SearchRng;
+
+impl RngCore for SearchRng {
+  fn next_u32(&mut self) -> u32 {
+    THREAD_RNG.with(|rng| rng.borrow_mut().next_u32())
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    THREAD_RNG.with(|rng| rng.borrow_mut().next_u64())
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    THREAD_RNG.with(|rng| rng.borrow_mut().fill_bytes(dest))
+  }
+}
+
+pub fn rng() -> SearchRng {
+  SearchRng
+}
+
+/// Reseed this thread's generator so the following run is repeatable
+pub fn seed(seed: u64) {
+  THREAD_RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// Seed for stream `stream` of a run seeded with `seed`, so each thread of
+/// a run draws different but repeatable numbers (SplitMix64 finaliser)
+pub fn derive_seed(seed: u64, stream: u64) -> u64 {
+  let mut z = seed ^ stream.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+  z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  z ^ (z >> 31)
+}
+
+/// Sample from a standard normal distribution (Box-Muller transform)
+pub fn standard_normal() -> f32 {
+  // 1 - u keeps the argument of ln in (0, 1]
+  let u1: f32 = 1.0 - rng().random::<f32>();
+  let u2: f32 = rng().random();
+  (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+/// Sample from a standard Cauchy distribution (heavy tailed)
+pub fn standard_cauchy() -> f32 {
+  let u: f32 = rng().random_range(0.0..1.0);
+  (PI * (u - 0.5)).tan()
+}
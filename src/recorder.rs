@@ -0,0 +1,185 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+
+use crate::observer::{Control, Observer, RunStatus};
+use crate::types::Image;
+
+// 1 (best) to 30 (fastest); NeuQuant at 1 is too slow for hundreds of frames
+const GIF_SPEED: i32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationFormat {
+  Gif,
+  /// Animated PNG: lossless, larger than GIF
+  Apng,
+}
+
+impl AnimationFormat {
+  pub fn name(self) -> &'static str {
+    match self {
+      AnimationFormat::Gif => "gif",
+      AnimationFormat::Apng => "apng",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<AnimationFormat> {
+    match name {
+      "gif" => Some(AnimationFormat::Gif),
+      "apng" => Some(AnimationFormat::Apng),
+      _ => None,
+    }
+  }
+
+  /// APNG for .png/.apng paths, GIF otherwise
+  pub fn from_path(path: &str) -> AnimationFormat {
+    if path.ends_with(".png") || path.ends_with(".apng") {
+      AnimationFormat::Apng
+    } else {
+      AnimationFormat::Gif
+    }
+  }
+}
+
+/// When the recorder captures a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capture {
+  /// Every n iterations
+  Interval(usize),
+  /// Whenever the best score improves
+  OnImprovement,
+}
+
+#[derive(Clone, Debug)]
+pub struct RecorderConfig {
+  pub format: AnimationFormat,
+  pub capture: Capture,
+  pub fps: u16,
+  /// Frame limit. Once reached, every other frame is dropped and capture
+  /// slows to half the rate, so the animation still spans the whole run.
+  pub max_frames: usize,
+}
+
+impl Default for RecorderConfig {
+  fn default() -> Self {
+    Self {
+      format: AnimationFormat::Gif,
+      capture: Capture::Interval(100),
+      fps: 10,
+      max_frames: 200,
+    }
+  }
+}
+
+/// Encode `frames` as a looping animation at `fps` frames per second
+pub fn write_animation(path: &Path, frames: &[RgbaImage], format: AnimationFormat, fps: u16) -> io::Result<()> {
+  if frames.is_empty() {
+    return Err(io::Error::new(io::ErrorKind::InvalidInput, "no frames to write"));
+  }
+  let file = BufWriter::new(File::create(path)?);
+  match format {
+    AnimationFormat::Gif => {
+      let delay = Delay::from_numer_denom_ms(1000, fps.max(1) as u32);
+      let mut encoder = GifEncoder::new_with_speed(file, GIF_SPEED);
+      encoder.set_repeat(Repeat::Infinite).map_err(io::Error::other)?;
+      encoder
+        .encode_frames(
+          frames
+            .iter()
+            .map(|frame| Frame::from_parts(frame.clone(), 0, 0, delay)),
+        )
+        .map_err(io::Error::other)
+    }
+    AnimationFormat::Apng => {
+      let (width, height) = frames[0].dimensions();
+      let mut encoder = png::Encoder::new(file, width, height);
+      encoder.set_color(png::ColorType::Rgba);
+      encoder.set_depth(png::BitDepth::Eight);
+      // 0 plays loops forever
+      encoder
+        .set_animated(frames.len() as u32, 0)
+        .map_err(io::Error::other)?;
+      encoder.set_frame_delay(1, fps.max(1)).map_err(io::Error::other)?;
+      let mut writer = encoder.write_header().map_err(io::Error::other)?;
+      for frame in frames {
+        writer.write_image_data(frame.as_raw()).map_err(io::Error::other)?;
+      }
+      writer.finish().map_err(io::Error::other)
+    }
+  }
+}
+
+/// Records the best genome over a run and writes a time-lapse animation
+/// when the run ends
+pub struct Recorder {
+  path: PathBuf,
+  config: RecorderConfig,
+  render: fn(&Image) -> RgbaImage,
+  frames: Vec<RgbaImage>,
+  /// Capture every `stride`-th event; doubles each time frames are thinned
+  stride: usize,
+  events: usize,
+  last_score: f32,
+  last_captured: Option<usize>,
+}
+
+impl Recorder {
+  /// `render` should match the renderer the run scores with
+  pub fn new(path: impl Into<PathBuf>, config: RecorderConfig, render: fn(&Image) -> RgbaImage) -> Self {
+    Self {
+      path: path.into(),
+      config,
+      render,
+      frames: Vec::new(),
+      stride: 1,
+      events: 0,
+      last_score: f32::NEG_INFINITY,
+      last_captured: None,
+    }
+  }
+
+  fn capture(&mut self, status: &RunStatus) {
+    if self.last_captured == Some(status.iteration) {
+      return;
+    }
+    if self.frames.len() >= self.config.max_frames.max(2) {
+      let mut index = 0;
+      self.frames.retain(|_| {
+        index += 1;
+        index % 2 == 1
+      });
+      self.stride *= 2;
+    }
+    self.frames.push((self.render)(status.best));
+    self.last_captured = Some(status.iteration);
+  }
+}
+
+impl Observer for Recorder {
+  fn on_iteration(&mut self, status: &RunStatus) -> Control {
+    let event = match self.config.capture {
+      Capture::Interval(interval) => status.iteration.is_multiple_of(interval.max(1)),
+      Capture::OnImprovement => status.best_score > self.last_score,
+    };
+    self.last_score = self.last_score.max(status.best_score);
+
+    if event {
+      if self.events.is_multiple_of(self.stride) {
+        self.capture(status);
+      }
+      self.events += 1;
+    }
+    Control::Continue
+  }
+
+  fn on_finish(&mut self, status: &RunStatus) {
+    // Always end on the final result
+    self.capture(status);
+    if let Err(err) = write_animation(&self.path, &self.frames, self.config.format, self.config.fps) {
+      eprintln!("Could not save animation {}: {}", self.path.display(), err);
+    }
+  }
+}
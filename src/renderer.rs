@@ -20,3 +20,8 @@ pub fn render_image(img: &Image) -> RgbaImage {
 
   canvas
 }
+
+/// Render at `width` x `height` whatever size the genome was evolved at
+pub fn render_image_at(img: &Image, width: usize, height: usize) -> RgbaImage {
+  render_image(&img.rescaled(width, height))
+}
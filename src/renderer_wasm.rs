@@ -1,6 +1,7 @@
 use crate::types::{Image, Polygon};
 
 /// Raw RGBA pixel buffer for WASM rendering
+#[derive(Clone)]
 pub struct PixelBuffer {
     pub data: Vec<u8>,
     pub width: usize,
@@ -28,17 +29,21 @@ impl PixelBuffer {
 
         let idx = (y * self.width + x) * 4;
         let alpha = color[3] as f32 / 255.0;
-        let inv_alpha = 1.0 - alpha;
 
-        self.data[idx] = (color[0] as f32 * alpha + self.data[idx] as f32 * inv_alpha) as u8;
-        self.data[idx + 1] =
-            (color[1] as f32 * alpha + self.data[idx + 1] as f32 * inv_alpha) as u8;
-        self.data[idx + 2] =
-            (color[2] as f32 * alpha + self.data[idx + 2] as f32 * inv_alpha) as u8;
+        for (pixel, &value) in self.data[idx..idx + 3].iter_mut().zip(&color) {
+            *pixel = blend_channel(value, alpha, *pixel);
+        }
         self.data[idx + 3] = 255; // Fully opaque background
     }
 }
 
+/// One channel of `value` painted over `below` with `alpha`, truncated to
+/// 8 bits exactly as the renderer does
+#[inline]
+pub(crate) fn blend_channel(value: u8, alpha: f32, below: u8) -> u8 {
+    (value as f32 * alpha + below as f32 * (1.0 - alpha)) as u8
+}
+
 /// Edge structure for scanline algorithm
 #[derive(Clone)]
 struct Edge {
@@ -49,6 +54,31 @@ struct Edge {
 
 /// Scanline polygon fill algorithm
 fn fill_polygon(buffer: &mut PixelBuffer, polygon: &Polygon) {
+    let (width, height) = (buffer.width, buffer.height);
+    for_each_span(polygon, width, height, |y, x_start, x_end| {
+        for x in x_start..=x_end {
+            buffer.set_pixel_blended(x, y, polygon.colour);
+        }
+    });
+}
+
+/// Pixel indices (y * width + x) a polygon covers, in fill order
+pub fn polygon_coverage(polygon: &Polygon, width: usize, height: usize) -> Vec<usize> {
+    let mut pixels = Vec::new();
+    for_each_span(polygon, width, height, |y, x_start, x_end| {
+        pixels.extend((x_start..=x_end).map(|x| y * width + x));
+    });
+    pixels
+}
+
+/// Call `span(y, x_start, x_end)` for every inclusive horizontal run of
+/// pixels inside the polygon, clipped to the canvas
+fn for_each_span<F: FnMut(usize, usize, usize)>(
+    polygon: &Polygon,
+    width: usize,
+    height: usize,
+    mut span: F,
+) {
     if polygon.points.len() < 3 {
         return;
     }
@@ -66,7 +96,7 @@ fn fill_polygon(buffer: &mut PixelBuffer, polygon: &Polygon) {
         .map(|(_, y)| *y)
         .max()
         .unwrap_or(0)
-        .min(buffer.height as i32 - 1);
+        .min(height as i32 - 1);
 
     if y_min > y_max {
         return;
@@ -90,7 +120,7 @@ fn fill_polygon(buffer: &mut PixelBuffer, polygon: &Polygon) {
             (x1 as f32, y1, x0 as f32, y0)
         };
 
-        if y_lower >= buffer.height as i32 || y_upper < 0 {
+        if y_lower >= height as i32 || y_upper < 0 {
             continue;
         }
 
@@ -110,14 +140,14 @@ fn fill_polygon(buffer: &mut PixelBuffer, polygon: &Polygon) {
     let mut active_edges: Vec<Edge> = Vec::new();
 
     for y in y_min..=y_max {
-        if y < 0 || y >= buffer.height as i32 {
+        if y < 0 || y >= height as i32 {
             continue;
         }
 
         // Add new edges from edge table
         let bucket_idx = (y - y_min) as usize;
         if bucket_idx < edge_table.len() {
-            active_edges.extend(edge_table[bucket_idx].drain(..));
+            active_edges.append(&mut edge_table[bucket_idx]);
         }
 
         // Remove edges that end at this scanline
@@ -130,10 +160,10 @@ fn fill_polygon(buffer: &mut PixelBuffer, polygon: &Polygon) {
         let mut i = 0;
         while i + 1 < active_edges.len() {
             let x_start = (active_edges[i].x_current.ceil() as i32).max(0);
-            let x_end = (active_edges[i + 1].x_current.floor() as i32).min(buffer.width as i32 - 1);
+            let x_end = (active_edges[i + 1].x_current.floor() as i32).min(width as i32 - 1);
 
-            for x in x_start..=x_end {
-                buffer.set_pixel_blended(x as usize, y as usize, polygon.colour);
+            if x_start <= x_end {
+                span(y as usize, x_start as usize, x_end as usize);
             }
 
             i += 2;
@@ -157,10 +187,20 @@ pub fn render_image(img: &Image) -> PixelBuffer {
     buffer
 }
 
+/// Render at `width` x `height` whatever size the genome was evolved at
+pub fn render_image_at(img: &Image, width: usize, height: usize) -> PixelBuffer {
+    render_image(&img.rescaled(width, height))
+}
+
 /// Render into an existing buffer (reuse allocation)
 pub fn render_image_into(img: &Image, buffer: &mut PixelBuffer) {
     buffer.clear();
-    for polygon in &img.polygon {
+    draw_polygons(buffer, &img.polygon);
+}
+
+/// Paint `polygons` in order over whatever the buffer already holds
+pub fn draw_polygons(buffer: &mut PixelBuffer, polygons: &[Polygon]) {
+    for polygon in polygons {
         fill_polygon(buffer, polygon);
     }
 }
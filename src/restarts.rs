@@ -0,0 +1,192 @@
+use crate::types::Image;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RestartStrategy {
+  /// Apply `mutations` random mutations to `members` members after the best
+  /// `keep_best`; None perturbs all of them
+  Perturb {
+    mutations: usize,
+    keep_best: usize,
+    members: Option<usize>,
+  },
+  /// Start again from fresh random genomes; the best so far goes to the hall of fame
+  RandomRestart,
+  /// Raise the SA temperature back to `temperature`; other algorithms perturb instead
+  Reheat { temperature: f32 },
+  /// IPOP: random restart with the population multiplied by `growth`
+  Ipop { growth: f32 },
+}
+
+impl RestartStrategy {
+  /// Parse a strategy name using the default parameters for that strategy
+  pub fn from_name(name: &str) -> Option<RestartStrategy> {
+    match name {
+      "perturb" => Some(RestartStrategy::Perturb {
+        mutations: 20,
+        keep_best: 1,
+        members: None,
+      }),
+      "random" => Some(RestartStrategy::RandomRestart),
+      "reheat" => Some(RestartStrategy::Reheat { temperature: 0.1 }),
+      "ipop" => Some(RestartStrategy::Ipop { growth: 2.0 }),
+      _ => None,
+    }
+  }
+
+  /// Non-improving iterations before this strategy fires, unless overridden.
+  /// Starting over throws away more progress, so it waits longer.
+  pub fn stagnation_threshold(&self) -> usize {
+    match self {
+      RestartStrategy::Perturb { .. } | RestartStrategy::Reheat { .. } => 500,
+      RestartStrategy::RandomRestart | RestartStrategy::Ipop { .. } => 2000,
+    }
+  }
+}
+
+/// An algorithm's built-in stagnation response, used when no strategy is configured
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DefaultRestart {
+  pub strategy: RestartStrategy,
+  /// None restarts only when the algorithm forces it, e.g. SA freezing
+  pub stagnation_threshold: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RestartConfig {
+  /// None uses the algorithm's built-in strategy, if it has one
+  pub strategy: Option<RestartStrategy>,
+  /// Non-improving iterations before a restart; None uses the strategy's own
+  pub stagnation_threshold: Option<usize>,
+  /// Total restarts allowed in a run; None for unlimited
+  pub max_restarts: Option<usize>,
+  /// Best genomes kept across restarts
+  pub hall_of_fame_size: usize,
+}
+
+impl Default for RestartConfig {
+  fn default() -> Self {
+    Self {
+      strategy: None,
+      stagnation_threshold: None,
+      max_restarts: None,
+      hall_of_fame_size: 5,
+    }
+  }
+}
+
+/// Best genomes seen across restarts, best first
+#[derive(Clone, Default)]
+pub struct HallOfFame {
+  capacity: usize,
+  entries: Vec<(f32, Image)>,
+}
+
+impl HallOfFame {
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      capacity,
+      entries: Vec::new(),
+    }
+  }
+
+  pub fn insert(&mut self, score: f32, image: Image) {
+    if self.capacity == 0 {
+      return;
+    }
+    let idx = self.entries.partition_point(|(s, _)| *s >= score);
+    if idx < self.capacity {
+      self.entries.insert(idx, (score, image));
+      self.entries.truncate(self.capacity);
+    }
+  }
+
+  pub fn best(&self) -> Option<(f32, &Image)> {
+    self.entries.first().map(|(s, img)| (*s, img))
+  }
+
+  pub fn entries(&self) -> &[(f32, Image)] {
+    &self.entries
+  }
+}
+
+/// Counts stagnation and decides when the restart budget allows a restart
+#[derive(Clone)]
+pub struct RestartController {
+  config: RestartConfig,
+  stagnation: usize,
+  restarts: usize,
+  pub hall_of_fame: HallOfFame,
+}
+
+impl RestartController {
+  pub fn new(config: RestartConfig) -> Self {
+    Self {
+      hall_of_fame: HallOfFame::new(config.hall_of_fame_size),
+      config,
+      stagnation: 0,
+      restarts: 0,
+    }
+  }
+
+  /// Configured strategy, or the algorithm's `builtin` one; None when
+  /// neither applies and the algorithm never restarts
+  pub fn strategy(&self, builtin: Option<DefaultRestart>) -> Option<RestartStrategy> {
+    self.config.strategy.or(builtin.map(|builtin| builtin.strategy))
+  }
+
+  /// Stagnation threshold of the strategy in use; None when only `force`
+  /// triggers a restart
+  fn stagnation_threshold(&self, builtin: Option<DefaultRestart>) -> Option<usize> {
+    match (self.config.strategy, builtin) {
+      (Some(strategy), _) => Some(
+        self
+          .config
+          .stagnation_threshold
+          .unwrap_or(strategy.stagnation_threshold()),
+      ),
+      (None, Some(builtin)) => self.config.stagnation_threshold.or(builtin.stagnation_threshold),
+      (None, None) => None,
+    }
+  }
+
+  pub fn stagnation(&self) -> usize {
+    self.stagnation
+  }
+
+  pub fn restarts(&self) -> usize {
+    self.restarts
+  }
+
+  /// Record one iteration. Returns true when a restart should happen now,
+  /// either because the stagnation threshold was hit or `force` is set
+  /// (e.g. SA has frozen), and the restart budget is not exhausted.
+  /// `builtin` is the algorithm's default, as passed to `strategy`.
+  pub fn should_restart(
+    &mut self,
+    improved: bool,
+    force: bool,
+    builtin: Option<DefaultRestart>,
+  ) -> bool {
+    if improved {
+      self.stagnation = 0;
+    } else {
+      self.stagnation += 1;
+    }
+
+    if self.strategy(builtin).is_none() {
+      return false;
+    }
+    let stagnant = self
+      .stagnation_threshold(builtin)
+      .is_some_and(|threshold| self.stagnation > threshold);
+    let due = force || stagnant;
+    let budget_left = self.config.max_restarts.is_none_or(|max| self.restarts < max);
+    if due && budget_left {
+      self.stagnation = 0;
+      self.restarts += 1;
+      true
+    } else {
+      false
+    }
+  }
+}
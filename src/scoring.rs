@@ -1,9 +1,11 @@
+use std::cell::{Cell, RefCell};
+
 #[cfg(feature = "cli")]
 use image::RgbaImage;
 
+use crate::layer_cache::LayerCache;
 #[cfg(feature = "cli")]
 use crate::renderer::render_image;
-#[cfg(feature = "cli")]
 use crate::types::Image;
 
 #[cfg(feature = "cli")]
@@ -15,7 +17,7 @@ pub fn sad_compare(target: &RgbaImage, rendered: &RgbaImage) -> f32 {
         .as_raw()
         .iter()
         .zip(rendered.as_raw().iter())
-        .map(|(a, b)| (*a as i16 - *b as i16).abs() as u64)
+        .map(|(a, b)| (*a as i16 - *b as i16).unsigned_abs() as u64)
         .sum();
 
     let max_diff = target.as_raw().len() as u64 * 255;
@@ -66,7 +68,7 @@ pub fn sad_compare_raw(target: &[u8], rendered: &[u8]) -> f32 {
     let total_diff: u64 = target
         .iter()
         .zip(rendered.iter())
-        .map(|(a, b)| (*a as i16 - *b as i16).abs() as u64)
+        .map(|(a, b)| (*a as i16 - *b as i16).unsigned_abs() as u64)
         .sum();
 
     let max_diff = target.len() as u64 * 255;
@@ -92,3 +94,272 @@ pub fn mse_compare_raw(target: &[u8], rendered: &[u8]) -> f32 {
     let max_sq_diff = target.len() as u64 * 255 * 255;
     1.0 - (total_sq_diff as f32 / max_sq_diff as f32)
 }
+
+/// Similarity measure an optimiser maximises
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    /// Sum of absolute differences
+    Sad,
+    /// Mean squared error; punishes large errors more
+    Mse,
+}
+
+impl Metric {
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Sad => "sad",
+            Metric::Mse => "mse",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Metric> {
+        match name {
+            "sad" => Some(Metric::Sad),
+            "mse" => Some(Metric::Mse),
+            _ => None,
+        }
+    }
+
+    pub fn compare_raw(self, target: &[u8], rendered: &[u8]) -> f32 {
+        match self {
+            Metric::Sad => sad_compare_raw(target, rendered),
+            Metric::Mse => mse_compare_raw(target, rendered),
+        }
+    }
+}
+
+/// Sobel gradients (x, y) of premultiplied luminance, one per pixel, with
+/// border pixels repeated beyond the edge
+pub fn sobel_gradients(pixels: &[u8], width: usize, height: usize) -> Vec<(f32, f32)> {
+    let luminance: Vec<f32> = pixels
+        .chunks_exact(4)
+        .map(|p| {
+            (0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32) * p[3] as f32 / 255.0
+        })
+        .collect();
+    if luminance.len() != width * height {
+        return Vec::new();
+    }
+    let at = |x: usize, y: usize| luminance[y * width + x];
+
+    let mut gradients = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let (up, down) = (y.saturating_sub(1), (y + 1).min(height - 1));
+        for x in 0..width {
+            let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+            let gx = at(right, up) + 2.0 * at(right, y) + at(right, down)
+                - at(left, up)
+                - 2.0 * at(left, y)
+                - at(left, down);
+            let gy = at(left, down) + 2.0 * at(x, down) + at(right, down)
+                - at(left, up)
+                - 2.0 * at(x, up)
+                - at(right, up);
+            gradients.push((gx, gy));
+        }
+    }
+    gradients
+}
+
+/// Similarity of two gradient fields from 0.0 to 1.0: one minus the summed
+/// length of their differences over their summed lengths. Edges that are
+/// blurred, missing, misplaced or turned all count against it.
+pub fn edge_similarity(target: &[(f32, f32)], rendered: &[(f32, f32)]) -> f32 {
+    if target.len() != rendered.len() {
+        return 0.0;
+    }
+    let (difference, total) = target
+        .iter()
+        .zip(rendered)
+        .fold((0.0f32, 0.0f32), |(difference, total), (t, r)| {
+            (
+                difference + (t.0 - r.0).hypot(t.1 - r.1),
+                total + t.0.hypot(t.1) + r.0.hypot(r.1),
+            )
+        });
+    if total == 0.0 {
+        1.0
+    } else {
+        1.0 - difference / total
+    }
+}
+
+/// Gradient-domain term blended into the pixel metric, with the target's
+/// gradients computed once
+struct EdgeTerm {
+    weight: f32,
+    width: usize,
+    height: usize,
+    target: Vec<(f32, f32)>,
+}
+
+/// Mean distance the polygons of `image` have moved from `previous`, as a
+/// fraction of the canvas diagonal. Polygons are matched by index and
+/// vertices by position; a polygon only one genome has counts as moving a
+/// full diagonal.
+pub fn polygon_displacement(image: &Image, previous: &Image) -> f32 {
+    let count = image.polygon.len().max(previous.polygon.len());
+    if count == 0 {
+        return 0.0;
+    }
+    let diagonal = ((image.width * image.width + image.height * image.height) as f32).sqrt();
+
+    let moved: f32 = image
+        .polygon
+        .iter()
+        .zip(&previous.polygon)
+        .map(|(polygon, before)| {
+            let pairs = polygon.points.len().min(before.points.len());
+            if pairs == 0 {
+                return 1.0;
+            }
+            let total: f32 = polygon
+                .points
+                .iter()
+                .zip(&before.points)
+                .map(|((x, y), (bx, by))| ((x - bx).powi(2) + (y - by).powi(2)).sqrt())
+                .sum();
+            (total / pairs as f32 / diagonal).min(1.0)
+        })
+        .sum();
+    let unmatched = count - image.polygon.len().min(previous.polygon.len());
+
+    (moved + unmatched as f32) / count as f32
+}
+
+/// Discourages polygons from moving away from a previous genome, e.g. the
+/// last frame of a sequence, to avoid flicker
+#[derive(Clone)]
+pub struct TemporalPenalty {
+    pub previous: Image,
+    /// Accuracy lost per unit of `polygon_displacement`
+    pub weight: f32,
+}
+
+/// Renders genomes and scores them against a fixed target, counting every
+/// fitness evaluation so runs can be compared on equal budgets. Renders
+/// start from cached layers of the best genomes scored so far.
+pub struct Evaluator {
+    target_pixels: Vec<u8>,
+    metric: Metric,
+    temporal: Option<TemporalPenalty>,
+    edges: Option<EdgeTerm>,
+    layers: RefCell<LayerCache>,
+    evaluations: Cell<usize>,
+}
+
+impl Evaluator {
+    pub fn new(target_pixels: Vec<u8>, metric: Metric) -> Self {
+        Self {
+            target_pixels,
+            metric,
+            temporal: None,
+            edges: None,
+            layers: RefCell::new(LayerCache::new()),
+            evaluations: Cell::new(0),
+        }
+    }
+
+    /// Subtract a temporal-coherence penalty from every score
+    pub fn with_temporal_penalty(mut self, penalty: Option<TemporalPenalty>) -> Self {
+        self.temporal = penalty;
+        self
+    }
+
+    /// Blend an edge term into every score: `weight` of the Sobel
+    /// `edge_similarity` and the rest of the pixel metric. A weight of 0
+    /// turns it off.
+    pub fn with_edge_weight(mut self, weight: f32, width: usize, height: usize) -> Self {
+        self.set_edge_weight(weight, width, height);
+        self
+    }
+
+    pub fn set_edge_weight(&mut self, weight: f32, width: usize, height: usize) {
+        self.edges = (weight > 0.0).then(|| EdgeTerm {
+            weight: weight.min(1.0),
+            width,
+            height,
+            target: sobel_gradients(&self.target_pixels, width, height),
+        });
+    }
+
+    pub fn score(&self, image: &Image) -> f32 {
+        self.evaluations.set(self.evaluations.get() + 1);
+        let mut layers = self.layers.borrow_mut();
+        let rendered = layers.render(image);
+        let mut accuracy = self.metric.compare_raw(&self.target_pixels, &rendered.data);
+        if let Some(edges) = &self.edges {
+            let gradients = sobel_gradients(&rendered.data, edges.width, edges.height);
+            accuracy = (1.0 - edges.weight) * accuracy + edges.weight * edge_similarity(&edges.target, &gradients);
+        }
+        let score = match &self.temporal {
+            Some(penalty) => accuracy - penalty.weight * polygon_displacement(image, &penalty.previous),
+            None => accuracy,
+        };
+        layers.offer(image, score);
+        score
+    }
+
+    pub fn evaluations(&self) -> usize {
+        self.evaluations.get()
+    }
+
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    pub fn target_pixels(&self) -> &[u8] {
+        &self.target_pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opaque black canvas with a white vertical bar starting at column `x`
+    fn bar(x: usize, width: usize, height: usize) -> Vec<u8> {
+        let mut pixels = [0, 0, 0, 255].repeat(width * height);
+        for y in 0..height {
+            for column in x..(x + 3).min(width) {
+                pixels[(y * width + column) * 4..][..3].copy_from_slice(&[255, 255, 255]);
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn edge_term_is_zero_for_identical_images() {
+        let gradients = sobel_gradients(&bar(5, 16, 12), 16, 12);
+        assert!(gradients.iter().any(|&(gx, _)| gx != 0.0));
+        assert_eq!(1.0 - edge_similarity(&gradients, &gradients), 0.0);
+    }
+
+    #[test]
+    fn edge_term_grows_as_edges_shift() {
+        let target = sobel_gradients(&bar(5, 16, 12), 16, 12);
+        let term = |x| 1.0 - edge_similarity(&target, &sobel_gradients(&bar(x, 16, 12), 16, 12));
+
+        assert!(term(6) > 0.0);
+        assert!(term(8) > term(6));
+        assert!(term(12) <= 1.0);
+    }
+
+    #[test]
+    fn edge_weight_blends_into_the_pixel_metric() {
+        let (width, height) = (16, 12);
+        let image = Image {
+            polygon: Vec::new(),
+            width,
+            height,
+            step_size: 1.0,
+        };
+        // A blank render has no edges, so it loses the whole edge share
+        let target = bar(5, width, height);
+        let pixels = Evaluator::new(target.clone(), Metric::Sad).score(&image);
+        let blended = Evaluator::new(target, Metric::Sad)
+            .with_edge_weight(0.25, width, height)
+            .score(&image);
+        assert!((blended - 0.75 * pixels).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,58 @@
+use rand::prelude::*;
+
+use crate::random::rng;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selection {
+  /// Best of `size` individuals drawn uniformly at random
+  Tournament { size: usize },
+  /// Linear ranking: the best individual is `n` times likelier than the worst
+  Rank,
+}
+
+impl Selection {
+  /// Parse a selection name using the default parameters for that scheme
+  pub fn from_name(name: &str) -> Option<Selection> {
+    match name {
+      "tournament" => Some(Selection::Tournament { size: 3 }),
+      "rank" => Some(Selection::Rank),
+      _ => None,
+    }
+  }
+}
+
+/// Pick the index of a parent from a population sorted best first
+pub fn select_index<T>(sorted: &[(f32, T)], selection: Selection) -> usize {
+  match selection {
+    Selection::Tournament { size } => tournament_select(sorted, size),
+    Selection::Rank => rank_select(sorted.len()),
+  }
+}
+
+/// Tournament selection over `(score, individual)` pairs, higher score wins
+pub fn tournament_select<T>(population: &[(f32, T)], size: usize) -> usize {
+  let mut rng = rng();
+  let mut best = rng.random_range(0..population.len());
+  for _ in 1..size.max(1) {
+    let challenger = rng.random_range(0..population.len());
+    if population[challenger].0 > population[best].0 {
+      best = challenger;
+    }
+  }
+  best
+}
+
+/// Linear rank selection over a population of `len` sorted best first
+pub fn rank_select(len: usize) -> usize {
+  // Rank r (0 = best) has weight len - r; total weight is len * (len + 1) / 2
+  let total = len * (len + 1) / 2;
+  let mut pick = rng().random_range(0..total);
+  for rank in 0..len {
+    let weight = len - rank;
+    if pick < weight {
+      return rank;
+    }
+    pick -= weight;
+  }
+  len - 1
+}
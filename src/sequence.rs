@@ -0,0 +1,130 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, ImageReader, RgbaImage};
+
+use crate::algorithms_wasm::{AlgorithmConfig, AlgorithmState};
+use crate::observer::Observer;
+use crate::renderer_wasm::render_image;
+use crate::scoring::{polygon_displacement, TemporalPenalty};
+use crate::types::Image;
+
+const FRAME_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+/// Frames of an animated GIF, or the images in a directory in file name
+/// order. Every frame must have the same dimensions.
+pub fn load_frames(path: &Path) -> Result<Vec<RgbaImage>, String> {
+  let frames = if path.is_dir() {
+    let mut paths: Vec<_> = std::fs::read_dir(path)
+      .map_err(|err| format!("Cannot read {}: {}", path.display(), err))?
+      .filter_map(|entry| entry.ok().map(|e| e.path()))
+      .filter(|p| {
+        p.extension()
+          .and_then(|e| e.to_str())
+          .is_some_and(|e| FRAME_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+      })
+      .collect();
+    paths.sort();
+
+    paths
+      .iter()
+      .map(|p| {
+        ImageReader::open(p)
+          .map_err(|err| err.to_string())
+          .and_then(|reader| reader.decode().map_err(|err| err.to_string()))
+          .map(|img| img.to_rgba8())
+          .map_err(|err| format!("Cannot load {}: {}", p.display(), err))
+      })
+      .collect::<Result<Vec<_>, _>>()?
+  } else {
+    let file = File::open(path).map_err(|err| format!("Cannot open {}: {}", path.display(), err))?;
+    let decoder = GifDecoder::new(BufReader::new(file))
+      .map_err(|err| format!("Cannot decode {}: {}", path.display(), err))?;
+    decoder
+      .into_frames()
+      .collect_frames()
+      .map_err(|err| format!("Cannot decode {}: {}", path.display(), err))?
+      .into_iter()
+      .map(|frame| frame.into_buffer())
+      .collect()
+  };
+
+  let first = frames
+    .first()
+    .ok_or_else(|| format!("No frames found in {}", path.display()))?
+    .dimensions();
+  if let Some(i) = frames.iter().position(|f| f.dimensions() != first) {
+    return Err(format!("Frame {} is not {}x{} like the first frame", i, first.0, first.1));
+  }
+  Ok(frames)
+}
+
+pub struct SequenceConfig {
+  /// Used for every frame; its stopping rule applies per frame
+  pub algorithm: AlgorithmConfig,
+  /// Temporal-coherence penalty weight, 0 for none
+  pub temporal_weight: f32,
+}
+
+pub struct FrameResult {
+  pub genome: Image,
+  /// Metric accuracy against the frame, without the temporal penalty
+  pub accuracy: f32,
+  /// `polygon_displacement` from the previous frame's genome
+  pub displacement: f32,
+  pub iterations: usize,
+  pub evaluations: usize,
+}
+
+/// Approximate each frame in turn. Every frame after the first starts from
+/// the previous frame's best genome and, with a temporal weight, is
+/// penalised for moving polygons away from it. `observer` sees each frame's
+/// run; `on_frame` is called as each frame finishes.
+pub fn run_sequence<F: FnMut(usize, &FrameResult)>(
+  frames: &[RgbaImage],
+  config: &SequenceConfig,
+  observer: &mut dyn Observer,
+  mut on_frame: F,
+) -> Vec<FrameResult> {
+  let mut results: Vec<FrameResult> = Vec::with_capacity(frames.len());
+
+  for (index, frame) in frames.iter().enumerate() {
+    let (width, height) = frame.dimensions();
+    let previous = results.last().map(|r| r.genome.clone());
+
+    let mut algorithm = config.algorithm.clone();
+    if config.temporal_weight > 0.0 {
+      algorithm.temporal = previous.clone().map(|previous| TemporalPenalty {
+        previous,
+        weight: config.temporal_weight,
+      });
+    }
+
+    let mut state = AlgorithmState::new(frame.as_raw().clone(), width as usize, height as usize, algorithm);
+    if let Some(previous) = &previous {
+      state.inject(previous.clone());
+    }
+    state.run_observed(usize::MAX, observer);
+
+    let genome = state.best_image().clone();
+    let result = FrameResult {
+      accuracy: config
+        .algorithm
+        .metric
+        .compare_raw(frame.as_raw(), &render_image(&genome).data),
+      displacement: previous
+        .as_ref()
+        .map(|previous| polygon_displacement(&genome, previous))
+        .unwrap_or(0.0),
+      iterations: state.get_iteration(),
+      evaluations: state.evaluations(),
+      genome,
+    };
+    on_frame(index, &result);
+    results.push(result);
+  }
+
+  results
+}
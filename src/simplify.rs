@@ -0,0 +1,196 @@
+use std::cmp::Ordering;
+
+use crate::constraints::PolygonConstraints;
+use crate::palette::Palette;
+use crate::renderer_wasm::polygon_coverage;
+use crate::types::{Image, Polygon};
+
+#[derive(Clone, Debug)]
+pub struct SimplifyConfig {
+  /// Largest score loss accepted for any one removal or merge
+  pub tolerance: f32,
+  /// Polygons with the same vertex count whose matching vertices are all
+  /// within this many pixels are tried as one
+  pub merge_distance: f32,
+  /// Vertex removals must leave every polygon meeting these
+  pub constraints: PolygonConstraints,
+  /// Merged colours are snapped to this
+  pub palette: Option<Palette>,
+}
+
+impl Default for SimplifyConfig {
+  fn default() -> Self {
+    Self {
+      tolerance: 0.0001,
+      merge_distance: 2.0,
+      constraints: PolygonConstraints::default(),
+      palette: None,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SimplifyReport {
+  pub polygons_before: usize,
+  pub polygons_after: usize,
+  pub vertices_before: usize,
+  pub vertices_after: usize,
+  /// Pairs of near-duplicate polygons replaced by one
+  pub merged: usize,
+  pub score_before: f32,
+  pub score_after: f32,
+}
+
+impl SimplifyReport {
+  pub fn summary(&self) -> String {
+    let reduction = |before: usize, after: usize| 100.0 * (before - after) as f32 / before.max(1) as f32;
+    format!(
+      "{} -> {} polygons (-{:.1}%, {} merged), {} -> {} vertices (-{:.1}%); accuracy {:.4}% -> {:.4}%",
+      self.polygons_before,
+      self.polygons_after,
+      reduction(self.polygons_before, self.polygons_after),
+      self.merged,
+      self.vertices_before,
+      self.vertices_after,
+      reduction(self.vertices_before, self.vertices_after),
+      self.score_before * 100.0,
+      self.score_after * 100.0
+    )
+  }
+}
+
+fn vertex_count(image: &Image) -> usize {
+  image.polygon.iter().map(|p| p.points.len()).sum()
+}
+
+/// The image keeping only the polygons marked in `keep`
+fn masked(image: &Image, keep: &[bool]) -> Image {
+  Image {
+    polygon: image
+      .polygon
+      .iter()
+      .zip(keep)
+      .filter(|(_, &keep)| keep)
+      .map(|(polygon, _)| polygon.clone())
+      .collect(),
+    width: image.width,
+    height: image.height,
+    step_size: image.step_size,
+  }
+}
+
+/// `upper` drawn over `lower` as a single polygon: their vertices averaged
+/// and the colour both would composite to. None unless the outlines match
+/// within `distance` at some rotation of `lower`'s vertex order.
+fn merge_pair(lower: &Polygon, upper: &Polygon, distance: f32) -> Option<Polygon> {
+  let n = upper.points.len();
+  if n == 0 || lower.points.len() != n {
+    return None;
+  }
+  let close = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) <= distance * distance;
+  let shift = (0..n).find(|&s| (0..n).all(|k| close(lower.points[(k + s) % n], upper.points[k])))?;
+
+  let points = (0..n)
+    .map(|k| {
+      let (a, b) = (lower.points[(k + shift) % n], upper.points[k]);
+      ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0)
+    })
+    .collect();
+
+  let (a_lower, a_upper) = (lower.colour[3] as f32 / 255.0, upper.colour[3] as f32 / 255.0);
+  let alpha = a_upper + a_lower * (1.0 - a_upper);
+  let mut colour = upper.colour;
+  if alpha > 0.0 {
+    for (channel, (u, l)) in colour.iter_mut().zip(upper.colour.iter().zip(&lower.colour)).take(3) {
+      let blended = (*u as f32 * a_upper + *l as f32 * a_lower * (1.0 - a_upper)) / alpha;
+      *channel = blended.round().clamp(0.0, 255.0) as u8;
+    }
+    colour[3] = (alpha * 255.0).round() as u8;
+  }
+
+  Some(Polygon { points, colour })
+}
+
+/// Greedily shrink a genome: drop polygons, least visible first, then merge
+/// near-duplicate pairs, then drop vertices, keeping each change that costs
+/// no more than `config.tolerance` of `score`. Covered, faint and
+/// zero-area polygons go for free.
+pub fn simplify<F: FnMut(&Image) -> f32>(image: Image, config: &SimplifyConfig, mut score: F) -> (Image, SimplifyReport) {
+  let mut current_score = score(&image);
+  let mut report = SimplifyReport {
+    polygons_before: image.polygon.len(),
+    vertices_before: vertex_count(&image),
+    score_before: current_score,
+    ..Default::default()
+  };
+  let mut accept = |candidate: &Image, current_score: &mut f32| {
+    let candidate_score = score(candidate);
+    let accepted = *current_score - candidate_score <= config.tolerance;
+    if accepted {
+      *current_score = candidate_score;
+    }
+    accepted
+  };
+
+  // Polygons, in order of visible pixels weighted by alpha
+  let visibility = |polygon: &Polygon| {
+    polygon_coverage(polygon, image.width, image.height).len() as f32 * polygon.colour[3] as f32
+  };
+  let mut order: Vec<(f32, usize)> = image.polygon.iter().map(visibility).zip(0..).collect();
+  order.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+  let mut keep = vec![true; image.polygon.len()];
+  for (_, index) in order {
+    keep[index] = false;
+    if !accept(&masked(&image, &keep), &mut current_score) {
+      keep[index] = true;
+    }
+  }
+  let mut current = masked(&image, &keep);
+
+  // Near-duplicate pairs, merged into the upper polygon's place
+  let mut i = 0;
+  while i < current.polygon.len() {
+    let mut merged = false;
+    for j in i + 1..current.polygon.len() {
+      let Some(mut polygon) = merge_pair(&current.polygon[i], &current.polygon[j], config.merge_distance) else {
+        continue;
+      };
+      if let Some(palette) = &config.palette {
+        let [r, g, b, a] = polygon.colour;
+        let [r, g, b] = palette.nearest([r, g, b]);
+        polygon.colour = [r, g, b, a];
+      }
+      let mut candidate = current.clone();
+      candidate.polygon[j] = polygon;
+      candidate.polygon.remove(i);
+      if accept(&candidate, &mut current_score) {
+        current = candidate;
+        report.merged += 1;
+        merged = true;
+        break;
+      }
+    }
+    // After a merge index i holds the next polygon
+    if !merged {
+      i += 1;
+    }
+  }
+
+  // Vertices
+  for p in 0..current.polygon.len() {
+    let mut v = 0;
+    while v < current.polygon[p].points.len() && current.polygon[p].points.len() > 3 {
+      let removed = current.polygon[p].points.remove(v);
+      if config.constraints.allows(&current.polygon[p].points) && accept(&current, &mut current_score) {
+        continue;
+      }
+      current.polygon[p].points.insert(v, removed);
+      v += 1;
+    }
+  }
+
+  report.polygons_after = current.polygon.len();
+  report.vertices_after = vertex_count(&current);
+  report.score_after = current_score;
+  (current, report)
+}
@@ -0,0 +1,246 @@
+use std::collections::VecDeque;
+
+/// A single reason to end a run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Criterion {
+  Iterations(usize),
+  /// Best accuracy reached
+  Accuracy(f32),
+  /// Seconds since the run started
+  WallClock(f64),
+  /// Fitness evaluations (renders scored)
+  Evaluations(usize),
+  /// Best accuracy gained less than `min_gain` over the last `window` evaluations
+  Plateau { min_gain: f32, window: usize },
+}
+
+impl Criterion {
+  /// Parse one `name=value` term: iterations=N, accuracy=X, time=SECONDS,
+  /// evaluations=N or plateau=GAIN/EVALUATIONS
+  pub fn parse(term: &str) -> Result<Criterion, String> {
+    let (name, value) = term
+      .split_once('=')
+      .ok_or_else(|| format!("Expected <criterion>=<value>, got {}", term))?;
+    let invalid = || format!("Invalid value for {}: {}", name, value);
+
+    match name {
+      "iterations" => value.parse().map(Criterion::Iterations).map_err(|_| invalid()),
+      "accuracy" => value.parse().map(Criterion::Accuracy).map_err(|_| invalid()),
+      "time" => value.parse().map(Criterion::WallClock).map_err(|_| invalid()),
+      "evaluations" => value.parse().map(Criterion::Evaluations).map_err(|_| invalid()),
+      "plateau" => {
+        let (gain, window) = value.split_once('/').ok_or_else(invalid)?;
+        Ok(Criterion::Plateau {
+          min_gain: gain.parse().map_err(|_| invalid())?,
+          window: window.parse().map_err(|_| invalid())?,
+        })
+      }
+      _ => Err(format!("Unknown stopping criterion: {}", name)),
+    }
+  }
+}
+
+/// Criteria combined as an OR of AND groups: the run stops as soon as every
+/// criterion in any one group holds
+#[derive(Clone, Debug, PartialEq)]
+pub struct StopCondition {
+  pub any_of: Vec<Vec<Criterion>>,
+}
+
+impl StopCondition {
+  /// Stop when any one of `criteria` holds
+  pub fn any(criteria: Vec<Criterion>) -> Self {
+    Self {
+      any_of: criteria.into_iter().map(|c| vec![c]).collect(),
+    }
+  }
+
+  /// Stop only once all of `criteria` hold
+  pub fn all(criteria: Vec<Criterion>) -> Self {
+    Self {
+      any_of: vec![criteria],
+    }
+  }
+
+  /// Stop when either condition would
+  pub fn or(mut self, other: StopCondition) -> Self {
+    self.any_of.extend(other.any_of);
+    self
+  }
+
+  /// Parse terms joined by `&` (AND) and `|` (OR), AND binding tighter,
+  /// e.g. "time=60 | evaluations=100000 & plateau=0.001/5000"
+  pub fn parse(spec: &str) -> Result<StopCondition, String> {
+    let any_of = spec
+      .split('|')
+      .map(|group| {
+        group
+          .split('&')
+          .map(|term| Criterion::parse(term.trim()))
+          .collect::<Result<Vec<_>, _>>()
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    Ok(StopCondition { any_of })
+  }
+}
+
+/// What a stopping check can see of a run
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+  pub iterations: usize,
+  pub evaluations: usize,
+  pub accuracy: f32,
+}
+
+/// Elapsed wall-clock time. `Instant` is unavailable in the browser, so
+/// WASM builds read the JavaScript clock instead.
+#[derive(Clone)]
+struct Clock {
+  #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+  start: std::time::Instant,
+  #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+  start_ms: f64,
+}
+
+impl Clock {
+  #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+  fn start() -> Self {
+    Self {
+      start: std::time::Instant::now(),
+    }
+  }
+
+  #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+  fn start() -> Self {
+    Self {
+      start_ms: js_sys::Date::now(),
+    }
+  }
+
+  #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+  fn elapsed_seconds(&self) -> f64 {
+    self.start.elapsed().as_secs_f64()
+  }
+
+  #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+  fn elapsed_seconds(&self) -> f64 {
+    (js_sys::Date::now() - self.start_ms) / 1000.0
+  }
+}
+
+/// Evaluates a `StopCondition` over a run, keeping the clock and the
+/// accuracy history that plateau detection needs
+#[derive(Clone)]
+pub struct StopTracker {
+  condition: StopCondition,
+  clock: Clock,
+  /// (evaluations, best accuracy) samples covering the longest plateau window
+  history: VecDeque<(usize, f32)>,
+  longest_window: usize,
+}
+
+impl StopTracker {
+  /// Start tracking; the wall clock starts now
+  pub fn new(condition: StopCondition) -> Self {
+    let longest_window = condition
+      .any_of
+      .iter()
+      .flatten()
+      .filter_map(|c| match c {
+        Criterion::Plateau { window, .. } => Some(*window),
+        _ => None,
+      })
+      .max()
+      .unwrap_or(0);
+
+    Self {
+      condition,
+      clock: Clock::start(),
+      history: VecDeque::new(),
+      longest_window,
+    }
+  }
+
+  pub fn elapsed_seconds(&self) -> f64 {
+    self.clock.elapsed_seconds()
+  }
+
+  /// Record the latest progress and report whether the run should stop
+  pub fn should_stop(&mut self, progress: &Progress) -> bool {
+    if self.longest_window > 0 {
+      self.history.push_back((progress.evaluations, progress.accuracy));
+      // Keep the newest sample at or before the start of the longest window
+      let horizon = progress.evaluations.saturating_sub(self.longest_window);
+      while self.history.len() > 1 && self.history[1].0 <= horizon {
+        self.history.pop_front();
+      }
+    }
+
+    let elapsed = self.clock.elapsed_seconds();
+    self
+      .condition
+      .any_of
+      .iter()
+      .any(|group| group.iter().all(|c| self.holds(c, progress, elapsed)))
+  }
+
+  fn holds(&self, criterion: &Criterion, progress: &Progress, elapsed: f64) -> bool {
+    match *criterion {
+      Criterion::Iterations(max) => progress.iterations >= max,
+      Criterion::Accuracy(target) => progress.accuracy >= target,
+      Criterion::WallClock(seconds) => elapsed >= seconds,
+      Criterion::Evaluations(max) => progress.evaluations >= max,
+      Criterion::Plateau { min_gain, window } => {
+        if progress.evaluations < window {
+          return false;
+        }
+        let horizon = progress.evaluations - window;
+        // Latest sample from at least `window` evaluations ago
+        match self.history.iter().rev().find(|(evaluations, _)| *evaluations <= horizon) {
+          Some((_, accuracy)) => progress.accuracy - accuracy < min_gain,
+          None => false,
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_single_criterion() {
+    assert_eq!(
+      StopCondition::parse("iterations=20"),
+      Ok(StopCondition::any(vec![Criterion::Iterations(20)]))
+    );
+  }
+
+  #[test]
+  fn parse_and_binds_tighter_than_or() {
+    let condition = StopCondition::parse("time=60 | evaluations=100000 & plateau=0.001/5000").unwrap();
+    assert_eq!(
+      condition.any_of,
+      vec![
+        vec![Criterion::WallClock(60.0)],
+        vec![
+          Criterion::Evaluations(100000),
+          Criterion::Plateau {
+            min_gain: 0.001,
+            window: 5000,
+          },
+        ],
+      ]
+    );
+  }
+
+  #[test]
+  fn parse_rejects_bad_terms() {
+    assert!(StopCondition::parse("iterations").is_err());
+    assert!(StopCondition::parse("iterations=many").is_err());
+    assert!(StopCondition::parse("plateau=0.001").is_err());
+    assert!(StopCondition::parse("generations=5").is_err());
+    assert!(StopCondition::parse("accuracy=0.9 &").is_err());
+  }
+}
@@ -0,0 +1,212 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::algorithms_wasm::{AlgorithmConfig, AlgorithmState};
+use crate::observer::Observer;
+use crate::random::{self, derive_seed};
+use crate::stopping::{Criterion, StopCondition};
+use crate::types::{Image, Polygon};
+
+#[derive(Clone, Debug)]
+pub struct TileConfig {
+  /// Side of each tile's own region in pixels
+  pub tile_size: usize,
+  /// Context each tile also sees on every side, so shapes crossing a seam
+  /// are fitted from both tiles
+  pub overlap: usize,
+  pub workers: usize,
+  /// Iterations of a global pass over the merged genome, 0 to skip
+  pub refine_iterations: usize,
+  /// Seeds the generator before each tile, so a run
+  /// is repeatable whichever worker takes a tile; None draws from entropy
+  pub seed: Option<u64>,
+}
+
+impl Default for TileConfig {
+  fn default() -> Self {
+    Self {
+      tile_size: 256,
+      overlap: 16,
+      workers: 1,
+      refine_iterations: 0,
+      seed: None,
+    }
+  }
+}
+
+/// A tile's own region (`core`) and the larger region it is optimised on
+/// (`context`), both as (x, y, width, height)
+#[derive(Clone, Copy, Debug)]
+pub struct Tile {
+  pub core: (usize, usize, usize, usize),
+  pub context: (usize, usize, usize, usize),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TileReport {
+  pub index: usize,
+  pub tiles: usize,
+  /// Accuracy on the tile's context region
+  pub accuracy: f32,
+}
+
+/// Grid of tiles covering a `width` x `height` image
+pub fn layout(width: usize, height: usize, config: &TileConfig) -> Vec<Tile> {
+  let size = config.tile_size.max(1);
+  let mut tiles = Vec::new();
+  for y in (0..height).step_by(size) {
+    for x in (0..width).step_by(size) {
+      let core = (x, y, size.min(width - x), size.min(height - y));
+      let cx = x.saturating_sub(config.overlap);
+      let cy = y.saturating_sub(config.overlap);
+      let cx1 = (x + core.2 + config.overlap).min(width);
+      let cy1 = (y + core.3 + config.overlap).min(height);
+      tiles.push(Tile {
+        core,
+        context: (cx, cy, cx1 - cx, cy1 - cy),
+      });
+    }
+  }
+  tiles
+}
+
+fn crop(pixels: &[u8], width: usize, region: (usize, usize, usize, usize)) -> Vec<u8> {
+  let (x, y, w, h) = region;
+  let mut out = Vec::with_capacity(w * h * 4);
+  for row in y..y + h {
+    let start = (row * width + x) * 4;
+    out.extend_from_slice(&pixels[start..start + w * 4]);
+  }
+  out
+}
+
+/// Sutherland-Hodgman clip of `points` to the half-plane where `inside` holds;
+/// `cross` gives the boundary crossing between an inside and outside point
+fn clip_edge(
+  points: &[(f32, f32)],
+  inside: impl Fn((f32, f32)) -> bool,
+  cross: impl Fn((f32, f32), (f32, f32)) -> (f32, f32),
+) -> Vec<(f32, f32)> {
+  let mut out = Vec::with_capacity(points.len() + 2);
+  for (i, &current) in points.iter().enumerate() {
+    let previous = points[(i + points.len() - 1) % points.len()];
+    match (inside(previous), inside(current)) {
+      (true, true) => out.push(current),
+      (true, false) => out.push(cross(previous, current)),
+      (false, true) => {
+        out.push(cross(previous, current));
+        out.push(current);
+      }
+      (false, false) => {}
+    }
+  }
+  out
+}
+
+/// Clip a polygon to the rectangle x0 <= x <= x1, y0 <= y <= y1
+fn clip_polygon(points: &[(f32, f32)], x0: f32, y0: f32, x1: f32, y1: f32) -> Vec<(f32, f32)> {
+  let at_x = |x: f32| {
+    move |(ax, ay): (f32, f32), (bx, by): (f32, f32)| (x, ay + (by - ay) * (x - ax) / (bx - ax))
+  };
+  let at_y = |y: f32| {
+    move |(ax, ay): (f32, f32), (bx, by): (f32, f32)| (ax + (bx - ax) * (y - ay) / (by - ay), y)
+  };
+
+  let points = clip_edge(points, |(x, _)| x >= x0, at_x(x0));
+  let points = clip_edge(&points, |(x, _)| x <= x1, at_x(x1));
+  let points = clip_edge(&points, |(_, y)| y >= y0, at_y(y0));
+  clip_edge(&points, |(_, y)| y <= y1, at_y(y1))
+}
+
+/// Move a tile's polygons into image coordinates, clipped to its core so
+/// neighbouring tiles never paint the same pixel. The scanline renderer
+/// fills columns inclusively and rows up to but excluding the last, hence
+/// the different right and bottom limits.
+fn place_tile(genome: &Image, tile: &Tile) -> Vec<Polygon> {
+  let (ox, oy) = (tile.context.0 as f32, tile.context.1 as f32);
+  let (x, y, w, h) = tile.core;
+  let (x0, y0) = (x as f32, y as f32);
+  let (x1, y1) = ((x + w) as f32 - 1.0, (y + h) as f32);
+
+  genome
+    .polygon
+    .iter()
+    .filter_map(|polygon| {
+      let points: Vec<(f32, f32)> = polygon.points.iter().map(|(px, py)| (px + ox, py + oy)).collect();
+      let points = clip_polygon(&points, x0, y0, x1, y1);
+      (points.len() >= 3).then_some(Polygon {
+        points,
+        colour: polygon.colour,
+      })
+    })
+    .collect()
+}
+
+/// Approximate each tile independently on `config.workers` threads and merge
+/// the tiles into one genome, optionally refined by a global pass.
+/// `on_tile` is called as each tile finishes; `observer` sees the global
+/// pass.
+pub fn run_tiled<F: Fn(&TileReport) + Sync>(
+  target_pixels: &[u8],
+  width: usize,
+  height: usize,
+  algorithm: &AlgorithmConfig,
+  config: &TileConfig,
+  observer: &mut dyn Observer,
+  on_tile: F,
+) -> Image {
+  let tiles = layout(width, height, config);
+  let next = AtomicUsize::new(0);
+  let genomes: Mutex<Vec<Option<Image>>> = Mutex::new(vec![None; tiles.len()]);
+
+  thread::scope(|scope| {
+    for _ in 0..config.workers.clamp(1, tiles.len()) {
+      scope.spawn(|| loop {
+        let index = next.fetch_add(1, Ordering::Relaxed);
+        let Some(tile) = tiles.get(index) else {
+          break;
+        };
+
+        if let Some(seed) = config.seed {
+          random::seed(derive_seed(seed, index as u64));
+        }
+        let (_, _, w, h) = tile.context;
+        let mut state = AlgorithmState::new(crop(target_pixels, width, tile.context), w, h, algorithm.clone());
+        state.run_iterations(usize::MAX);
+
+        on_tile(&TileReport {
+          index,
+          tiles: tiles.len(),
+          accuracy: state.get_accuracy(),
+        });
+        genomes.lock().unwrap()[index] = Some(state.best_image().clone());
+      });
+    }
+  });
+
+  let genomes = genomes.into_inner().unwrap();
+  let merged = Image {
+    polygon: tiles
+      .iter()
+      .zip(&genomes)
+      .filter_map(|(tile, genome)| genome.as_ref().map(|g| place_tile(g, tile)))
+      .flatten()
+      .collect(),
+    width,
+    height,
+    step_size: 1.0,
+  };
+
+  if config.refine_iterations == 0 {
+    return merged;
+  }
+
+  // Global pass over the whole image, warm-started from the merged tiles
+  let mut refine = algorithm.clone();
+  refine.stopping = Some(StopCondition::any(vec![Criterion::Iterations(config.refine_iterations)]));
+  let mut state = AlgorithmState::new(target_pixels.to_vec(), width, height, refine);
+  state.inject(merged);
+  state.run_observed(usize::MAX, observer);
+  state.best_image().clone()
+}
@@ -1,4 +1,4 @@
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Polygon {
   pub points: Vec<(f32, f32)>,
   pub colour: [u8; 4],
@@ -9,4 +9,29 @@ pub struct Image {
   pub polygon: Vec<Polygon>,
   pub width: usize,
   pub height: usize,
+  /// Self-adaptive mutation step multiplier carried by this individual
+  pub step_size: f32,
+}
+
+impl Image {
+  /// The same genome on a `width` x `height` canvas. Points are scaled with
+  /// the canvas, so a genome evolved on a small target can be rendered at
+  /// any size.
+  pub fn rescaled(&self, width: usize, height: usize) -> Image {
+    let sx = width as f32 / self.width.max(1) as f32;
+    let sy = height as f32 / self.height.max(1) as f32;
+    Image {
+      polygon: self
+        .polygon
+        .iter()
+        .map(|polygon| Polygon {
+          points: polygon.points.iter().map(|(x, y)| (x * sx, y * sy)).collect(),
+          colour: polygon.colour,
+        })
+        .collect(),
+      width,
+      height,
+      step_size: self.step_size,
+    }
+  }
 }
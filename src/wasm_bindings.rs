@@ -1,6 +1,14 @@
 use wasm_bindgen::prelude::*;
 
-use crate::algorithms_wasm::{AlgorithmConfig, AlgorithmState, AlgorithmType};
+use crate::algorithms_wasm::{AlgorithmConfig, AlgorithmState, AlgorithmType, Replacement};
+use crate::constraints::{PolygonConstraints, PolygonShape};
+use crate::crossover::CrossoverType;
+use crate::mutations::{MutationType, Perturbation, StepAdaptation, StepScale};
+use crate::palette::{Palette, Quantizer};
+use crate::renderer_wasm::render_image_at;
+use crate::restarts::{RestartConfig, RestartStrategy};
+use crate::selection::Selection;
+use crate::stopping::StopCondition;
 
 /// Initialize panic hook for better error messages
 #[wasm_bindgen(start)]
@@ -17,7 +25,8 @@ pub struct WasmAlgorithm {
 #[wasm_bindgen]
 impl WasmAlgorithm {
     /// Create a new algorithm instance with target image data
-    /// algorithm: 0 = Evolution Strategy, 1 = Simulated Annealing, 2 = Differential Evolution
+    /// algorithm: 0 = Evolution Strategy, 1 = Simulated Annealing, 2 = Differential Evolution,
+    /// 3 = Genetic Algorithm, 4 = CMA-ES, 5 = Particle Swarm, 6 = NSGA-II
     #[wasm_bindgen(constructor)]
     pub fn new(
         target_pixels: Vec<u8>,
@@ -30,6 +39,10 @@ impl WasmAlgorithm {
         let algorithm_type = match algorithm {
             1 => AlgorithmType::SimulatedAnnealing,
             2 => AlgorithmType::DifferentialEvolution,
+            3 => AlgorithmType::GeneticAlgorithm,
+            4 => AlgorithmType::CmaEs,
+            5 => AlgorithmType::ParticleSwarm,
+            6 => AlgorithmType::Nsga2,
             _ => AlgorithmType::EvolutionStrategy,
         };
 
@@ -49,7 +62,14 @@ impl WasmAlgorithm {
     #[wasm_bindgen]
     pub fn step(&mut self, batch_size: usize) -> Vec<u8> {
         let (_, buffer) = self.state.step_batch(batch_size);
-        buffer.data
+        buffer.data.clone()
+    }
+
+    /// Render the current best image at any size as RGBA bytes, e.g. to
+    /// optimise a small target while displaying at canvas resolution
+    #[wasm_bindgen]
+    pub fn render_at(&self, width: usize, height: usize) -> Vec<u8> {
+        render_image_at(self.state.best_image(), width, height).data
     }
 
     /// Get current iteration count
@@ -81,4 +101,259 @@ impl WasmAlgorithm {
     pub fn get_height(&self) -> usize {
         self.state.get_dimensions().1
     }
+
+    /// NSGA-II Pareto front as flattened (complexity, accuracy) pairs,
+    /// smallest genome first; empty for other algorithms
+    #[wasm_bindgen]
+    pub fn get_pareto_front(&self) -> Vec<f32> {
+        self.state
+            .pareto_front()
+            .iter()
+            .flat_map(|i| [i.complexity as f32, i.accuracy])
+            .collect()
+    }
+
+    /// Replace the iteration/accuracy limits with a stopping rule such as
+    /// "time=60 | evaluations=100000 & plateau=0.001/5000" (& binds tighter
+    /// than |). Returns false if the rule cannot be parsed
+    #[wasm_bindgen]
+    pub fn set_stop_condition(&mut self, spec: &str) -> bool {
+        match StopCondition::parse(spec) {
+            Ok(condition) => {
+                self.state.set_stop_condition(condition);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Least-squares fit the colours of the best genome for its current
+    /// shapes, running `sweeps` passes over all polygons
+    #[wasm_bindgen]
+    pub fn solve_colours(&mut self, sweeps: usize) {
+        self.state.solve_colours(sweeps);
+    }
+
+    /// Set the relative weight of a mutation operator by name (e.g. "move-point")
+    /// Returns false if the name is not a known operator
+    #[wasm_bindgen]
+    pub fn set_mutation_weight(&mut self, name: &str, weight: f32) -> bool {
+        match MutationType::from_name(name) {
+            Some(mutation_type) => {
+                self.state.mutation_config_mut().set_weight(mutation_type, weight);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the maximum point move, polygon move and colour channel deltas
+    /// Returns false, changing nothing, if any is negative or not finite
+    #[wasm_bindgen]
+    pub fn set_mutation_deltas(&mut self, point_move: f32, polygon_move: f32, colour: i16) -> bool {
+        self.state
+            .mutation_config_mut()
+            .set_deltas(point_move, polygon_move, colour)
+            .is_ok()
+    }
+
+    /// Set the perturbation distribution: "uniform", "gaussian" or "cauchy"
+    /// Returns false if the name is not recognised
+    #[wasm_bindgen]
+    pub fn set_perturbation(&mut self, name: &str) -> bool {
+        match Perturbation::from_name(name) {
+            Some(perturbation) => {
+                self.state.mutation_config_mut().perturbation = perturbation;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Interpret point/polygon deltas as fractions of the longest image
+    /// side; deltas left at their defaults switch to 0.02 and 0.01
+    #[wasm_bindgen]
+    pub fn set_relative_steps(&mut self, relative: bool) {
+        self.state.mutation_config_mut().set_step_scale(if relative {
+            StepScale::Relative
+        } else {
+            StepScale::Absolute
+        });
+    }
+
+    /// Set the step-size adaptation rule: "fixed", "one-fifth" or "self-adaptive"
+    /// Returns false if the name is not recognised
+    #[wasm_bindgen]
+    pub fn set_step_adaptation(&mut self, name: &str) -> bool {
+        match StepAdaptation::from_name(name) {
+            Some(adaptation) => {
+                self.state.mutation_config_mut().adaptation = adaptation;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Constrain polygons to a shape, "any", "simple" or "convex", and a
+    /// vertex limit (0 for none). Regenerates the population, so call
+    /// before stepping. Returns false if the shape is not recognised
+    #[wasm_bindgen]
+    pub fn set_polygon_constraints(&mut self, shape: &str, max_vertices: usize) -> bool {
+        match PolygonShape::from_name(shape) {
+            Some(shape) => {
+                self.state.set_polygon_constraints(PolygonConstraints {
+                    shape,
+                    max_vertices: (max_vertices > 0).then_some(max_vertices),
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Give `weight` (0 to 1) of the score to matching the target's Sobel
+    /// edges rather than its pixels. Regenerates the population, so call
+    /// before stepping
+    #[wasm_bindgen]
+    pub fn set_edge_weight(&mut self, weight: f32) {
+        self.state.set_edge_weight(weight);
+    }
+
+    /// Restrict polygon colours to a palette given as hex colours or a GIMP
+    /// palette; an empty string lifts the restriction. Regenerates the
+    /// population, so call before stepping. Returns false if it can't be parsed
+    #[wasm_bindgen]
+    pub fn set_palette(&mut self, text: &str) -> bool {
+        if text.trim().is_empty() {
+            self.state.set_palette(None);
+            return true;
+        }
+        match Palette::parse(text) {
+            Ok(palette) => {
+                self.state.set_palette(Some(palette));
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Restrict polygon colours to `size` colours extracted from the target
+    /// with "kmeans" or "median-cut", as for `set_palette`. Returns the
+    /// palette as hex colours, or an empty string if the method is unknown
+    #[wasm_bindgen]
+    pub fn use_target_palette(&mut self, size: usize, method: &str) -> String {
+        match Quantizer::from_name(method) {
+            Some(quantizer) => {
+                let palette = Palette::from_target(self.state.target_pixels(), size, quantizer);
+                let hex = palette.to_hex();
+                self.state.set_palette(Some(palette));
+                hex
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Set the GA population size. Regenerates the population, so call
+    /// before stepping
+    #[wasm_bindgen]
+    pub fn set_ga_population_size(&mut self, size: usize) {
+        self.state.set_ga_population_size(size);
+    }
+
+    /// Set the GA selection: "tournament" (size 3) or "rank"
+    /// Returns false if the name is not recognised
+    #[wasm_bindgen]
+    pub fn set_selection(&mut self, name: &str) -> bool {
+        match Selection::from_name(name) {
+            Some(selection) => {
+                self.state.config_mut().ga_selection = selection;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the number of individuals per tournament
+    /// Returns false, changing nothing, under rank selection
+    #[wasm_bindgen]
+    pub fn set_tournament_size(&mut self, size: usize) -> bool {
+        let config = self.state.config_mut();
+        match config.ga_selection {
+            Selection::Tournament { .. } => {
+                config.ga_selection = Selection::Tournament { size };
+                true
+            }
+            Selection::Rank => false,
+        }
+    }
+
+    /// Set the GA crossover: "uniform", "one-point", "two-point" or "spatial"
+    /// Returns false if the name is not recognised
+    #[wasm_bindgen]
+    pub fn set_crossover(&mut self, name: &str) -> bool {
+        match CrossoverType::from_name(name) {
+            Some(crossover_type) => {
+                self.state.config_mut().ga_crossover_type = crossover_type;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the chance a GA child is bred by crossover rather than copied
+    #[wasm_bindgen]
+    pub fn set_crossover_rate(&mut self, rate: f32) {
+        self.state.config_mut().ga_crossover_rate = rate;
+    }
+
+    /// Set the chance a GA child is mutated
+    #[wasm_bindgen]
+    pub fn set_mutation_rate(&mut self, rate: f32) {
+        self.state.config_mut().ga_mutation_rate = rate;
+    }
+
+    /// Set the number of GA individuals carried over unchanged
+    #[wasm_bindgen]
+    pub fn set_elitism(&mut self, elites: usize) {
+        self.state.config_mut().ga_elitism = elites;
+    }
+
+    /// Breed one GA child per step replacing the worst, instead of whole
+    /// generations
+    #[wasm_bindgen]
+    pub fn set_steady_state(&mut self, steady_state: bool) {
+        self.state.config_mut().ga_replacement = if steady_state {
+            Replacement::SteadyState
+        } else {
+            Replacement::Generational
+        };
+    }
+
+    /// Configure stagnation restarts. strategy: "perturb", "random", "reheat",
+    /// "ipop" or "" for the algorithm default; stagnation_threshold 0 uses the
+    /// strategy's own and max_restarts 0 means unlimited.
+    /// Returns false if the strategy name is not recognised
+    #[wasm_bindgen]
+    pub fn set_restart_strategy(
+        &mut self,
+        strategy: &str,
+        stagnation_threshold: usize,
+        max_restarts: usize,
+    ) -> bool {
+        let strategy = match strategy {
+            "" => None,
+            name => match RestartStrategy::from_name(name) {
+                Some(strategy) => Some(strategy),
+                None => return false,
+            },
+        };
+
+        self.state.set_restart_config(RestartConfig {
+            strategy,
+            stagnation_threshold: (stagnation_threshold > 0).then_some(stagnation_threshold),
+            max_restarts: (max_restarts > 0).then_some(max_restarts),
+            ..Default::default()
+        });
+        true
+    }
 }